@@ -0,0 +1,112 @@
+//! Compares the boxed `LinkedList` with the arena-backed `ArenaList` on the
+//! workloads the arena is meant for: building a list up, and churning
+//! elements through one that stays about the same length. Appending shows
+//! what the arena gives up in return: `LinkedList::append` relinks in O(1),
+//! while `ArenaList::append` moves every element across in O(n).
+//!
+//! Run with `cargo bench -p linked-list`.
+
+use criterion::{
+    black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput,
+};
+use linked_list::{ArenaList, LinkedList};
+
+const SIZES: [usize; 3] = [100, 10_000, 1_000_000];
+
+fn build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build");
+    for &size in &SIZES {
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::new("boxed", size), &size, |b, &size| {
+            b.iter(|| {
+                let mut list = LinkedList::empty();
+                for i in 0..size {
+                    list.push(black_box(i));
+                }
+                list
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("arena", size), &size, |b, &size| {
+            b.iter(|| {
+                let mut list = ArenaList::empty();
+                for i in 0..size {
+                    list.push(black_box(i));
+                }
+                list
+            })
+        });
+    }
+    group.finish();
+}
+
+// Pop from the front and push to the back of a list that is already full,
+// as a work queue does, so that the arena reuses every freed slot
+fn churn(c: &mut Criterion) {
+    let mut group = c.benchmark_group("churn");
+    for &size in &SIZES {
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::new("boxed", size), &size, |b, &size| {
+            let mut list: LinkedList<_> = (0..size).collect();
+            b.iter(|| {
+                for _ in 0..size {
+                    let element = list.pop().unwrap();
+                    list.push_back(black_box(element));
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("arena", size), &size, |b, &size| {
+            let mut list: ArenaList<_> = (0..size).collect();
+            b.iter(|| {
+                for _ in 0..size {
+                    let element = list.pop().unwrap();
+                    list.push_back(black_box(element));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+// Append a list of each size to a short one. Building both lists is left
+// out of the timings
+fn append(c: &mut Criterion) {
+    let mut group = c.benchmark_group("append");
+    for &size in &SIZES {
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::new("boxed", size), &size, |b, &size| {
+            b.iter_batched(
+                || ((0..10).collect::<LinkedList<_>>(), (0..size).collect()),
+                |(mut list, mut other)| {
+                    list.append(&mut other);
+                    list
+                },
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("arena", size), &size, |b, &size| {
+            b.iter_batched(
+                || ((0..10).collect::<ArenaList<_>>(), (0..size).collect()),
+                |(mut list, mut other)| {
+                    list.append(&mut other);
+                    list
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn iterate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("iterate");
+    let size: u64 = 1_000_000;
+    group.throughput(Throughput::Elements(size));
+    let boxed: LinkedList<_> = (0..size).collect();
+    group.bench_function("boxed", |b| b.iter(|| boxed.iter().sum::<u64>()));
+    let arena: ArenaList<_> = (0..size).collect();
+    group.bench_function("arena", |b| b.iter(|| arena.iter().sum::<u64>()));
+    group.finish();
+}
+
+criterion_group!(benches, build, churn, append, iterate);
+criterion_main!(benches);
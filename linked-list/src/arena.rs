@@ -0,0 +1,874 @@
+//! A singly linked list whose nodes live in a single growable arena.
+//!
+//! [`LinkedList`](crate::LinkedList) allocates every node on its own, so a
+//! list that is pushed to and popped from millions of times spends most of
+//! its time in the allocator. [`ArenaList`] instead keeps its nodes in one
+//! `Vec` and links them by index. Slots freed by `pop` are chained into a
+//! free list and reused by the next `push`, so once the arena has grown to
+//! the list's peak length, churning through it allocates nothing.
+//!
+//! It has the same API as `LinkedList`, though moving elements in from
+//! another list, with `append` or by splicing at a cursor, is O(n) in that
+//! list's length rather than O(1), as the elements have to move arenas.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::mem;
+
+pub struct ArenaList<T> {
+    slots: Vec<Slot<T>>,
+    head: Link,
+    tail: Link,
+    // The first of the vacant slots, each of which links to the next
+    free: Link,
+    len: usize,
+}
+
+type Link = Option<usize>;
+
+enum Slot<T> {
+    Occupied { element: T, next: Link },
+    Vacant { next_free: Link },
+}
+
+impl<T> ArenaList<T> {
+    pub fn empty() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// An empty list with room for `capacity` elements before the arena
+    /// has to grow.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            head: None,
+            tail: None,
+            free: None,
+            len: 0,
+        }
+    }
+
+    pub fn new(element: T) -> Self {
+        let mut list = Self::empty();
+        list.push(element);
+        list
+    }
+
+    /// The number of elements the list can hold before the arena has to
+    /// grow, including slots freed by earlier removals.
+    pub fn capacity(&self) -> usize {
+        self.slots.capacity() - self.slots.len() + self.vacant()
+    }
+
+    fn vacant(&self) -> usize {
+        self.slots.len() - self.len
+    }
+
+    pub fn push(&mut self, element: T) {
+        let index = self.alloc(element, self.head);
+        if self.head.is_none() {
+            self.tail = Some(index);
+        }
+        self.head = Some(index);
+    }
+
+    /// Add an element to the back of the list, so that it is popped last.
+    pub fn push_back(&mut self, element: T) {
+        let index = self.alloc(element, None);
+        match self.tail {
+            Some(tail) => self.set_next(tail, Some(index)),
+            None => self.head = Some(index),
+        }
+        self.tail = Some(index);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.head.map(|head| {
+            let (element, next) = self.release(head);
+            self.head = next;
+            if next.is_none() {
+                self.tail = None;
+            }
+            element
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Remove every element, keeping the arena's memory for reuse.
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.head = None;
+        self.tail = None;
+        self.free = None;
+        self.len = 0;
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.head.map(|i| self.element(i))
+    }
+
+    pub fn peek_mut(&mut self) -> Option<&mut T> {
+        let head = self.head?;
+        Some(self.element_mut(head))
+    }
+
+    /// The last element, reached through the tail without walking the list.
+    pub fn peek_back(&self) -> Option<&T> {
+        self.tail.map(|i| self.element(i))
+    }
+
+    pub fn peek_back_mut(&mut self) -> Option<&mut T> {
+        let tail = self.tail?;
+        Some(self.element_mut(tail))
+    }
+
+    /// The element at the given index, found by walking the list from the
+    /// head.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.slot_at(index).map(|i| self.element(i))
+    }
+
+    /// A mutable reference to the element at the given index, as with
+    /// [`ArenaList::get`].
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let slot = self.slot_at(index)?;
+        Some(self.element_mut(slot))
+    }
+
+    fn slot_at(&self, index: usize) -> Link {
+        if index >= self.len {
+            return None;
+        }
+        let mut link = self.head;
+        for _ in 0..index {
+            link = link.and_then(|i| self.next(i));
+        }
+        link
+    }
+
+    pub fn contains(&self, element: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        self.iter().any(|e| e == element)
+    }
+
+    /// The index of the first element that matches the predicate.
+    pub fn position<P: FnMut(&T) -> bool>(&self, predicate: P) -> Option<usize> {
+        self.iter().position(predicate)
+    }
+
+    /// Keep only the elements that match the predicate, removing the rest
+    /// in a single pass.
+    pub fn retain<P: FnMut(&T) -> bool>(&mut self, mut predicate: P) {
+        self.extract_if(|e| !predicate(e)).for_each(drop);
+    }
+
+    /// An iterator that removes and yields the elements that match the
+    /// predicate, in order. As with
+    /// [`LinkedList::extract_if`](crate::LinkedList::extract_if), elements
+    /// it doesn't get to before being dropped stay in the list.
+    pub fn extract_if<P: FnMut(&mut T) -> bool>(&mut self, predicate: P) -> ExtractIf<'_, T, P> {
+        ExtractIf {
+            cursor: self.cursor_front_mut(),
+            predicate,
+        }
+    }
+
+    /// A cursor starting at the head of the list, which can walk forwards
+    /// and edit the list as it goes.
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            prev: None,
+            list: self,
+        }
+    }
+
+    /// Iterate over references to the elements, from the head.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            slots: &self.slots,
+            next: self.head,
+        }
+    }
+
+    /// Iterate over mutable references to the elements, from the head.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            slots: self.slots.as_mut_ptr(),
+            next: self.head,
+            marker: PhantomData,
+        }
+    }
+
+    /// Reverse the order of the elements in place, by relinking the slots.
+    pub fn reverse(&mut self) {
+        let mut prev = None;
+        let mut link = self.head;
+        self.tail = self.head;
+        while let Some(i) = link {
+            link = self.next(i);
+            self.set_next(i, prev);
+            prev = Some(i);
+        }
+        self.head = prev;
+    }
+
+    /// Move every element of `other` to the back of this list, leaving
+    /// `other` empty. Unlike [`LinkedList::append`](crate::LinkedList::append)
+    /// this is O(n) in the length of `other`, as its elements move into
+    /// this list's arena.
+    pub fn append(&mut self, other: &mut Self) {
+        while let Some(element) = other.pop() {
+            self.push_back(element);
+        }
+    }
+
+    /// Split the list in two at the given index, returning everything from
+    /// that index on, in a new arena.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at` is greater than the length of the list.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        assert!(at <= self.len, "Cannot split off at a nonexistent index");
+        if at == 0 {
+            return mem::take(self);
+        }
+
+        let last = self.slot_at(at - 1).unwrap();
+        let mut link = self.next(last);
+        self.set_next(last, None);
+        self.tail = Some(last);
+
+        let mut split = Self::with_capacity(self.len - at);
+        while let Some(i) = link {
+            let (element, next) = self.release(i);
+            split.push_back(element);
+            link = next;
+        }
+        split
+    }
+
+    /// Sort the list with a stable sort, in O(n log n) time. Slots are
+    /// relinked rather than elements moved, though the order is worked out
+    /// in a `Vec` of indices.
+    pub fn sort(&mut self)
+    where
+        T: Ord,
+    {
+        self.sort_by(T::cmp);
+    }
+
+    /// Sort the list by the key each element maps to, as with
+    /// [`ArenaList::sort`].
+    pub fn sort_by_key<K: Ord, F: FnMut(&T) -> K>(&mut self, mut f: F) {
+        self.sort_by(|a, b| f(a).cmp(&f(b)));
+    }
+
+    /// Sort the list with a comparison function, as with
+    /// [`ArenaList::sort`].
+    pub fn sort_by<F: FnMut(&T, &T) -> Ordering>(&mut self, mut compare: F) {
+        let mut order = Vec::with_capacity(self.len);
+        let mut link = self.head;
+        while let Some(i) = link {
+            order.push(i);
+            link = self.next(i);
+        }
+        order.sort_by(|&a, &b| compare(self.element(a), self.element(b)));
+
+        self.head = order.first().copied();
+        self.tail = order.last().copied();
+        for pair in order.windows(2) {
+            self.set_next(pair[0], Some(pair[1]));
+        }
+        if let Some(tail) = self.tail {
+            self.set_next(tail, None);
+        }
+    }
+
+    fn alloc(&mut self, element: T, next: Link) -> usize {
+        let slot = Slot::Occupied { element, next };
+        self.len += 1;
+        match self.free {
+            Some(index) => {
+                match mem::replace(&mut self.slots[index], slot) {
+                    Slot::Vacant { next_free } => self.free = next_free,
+                    Slot::Occupied { .. } => unreachable!("free list holds an occupied slot"),
+                }
+                index
+            }
+            None => {
+                self.slots.push(slot);
+                self.slots.len() - 1
+            }
+        }
+    }
+
+    // Free a slot that has already been unlinked from the list, returning
+    // its element and the link it held
+    fn release(&mut self, index: usize) -> (T, Link) {
+        let vacant = Slot::Vacant {
+            next_free: self.free,
+        };
+        self.free = Some(index);
+        self.len -= 1;
+        match mem::replace(&mut self.slots[index], vacant) {
+            Slot::Occupied { element, next } => (element, next),
+            Slot::Vacant { .. } => unreachable!("list links to a vacant slot"),
+        }
+    }
+
+    fn element(&self, index: usize) -> &T {
+        match &self.slots[index] {
+            Slot::Occupied { element, .. } => element,
+            Slot::Vacant { .. } => unreachable!("list links to a vacant slot"),
+        }
+    }
+
+    fn element_mut(&mut self, index: usize) -> &mut T {
+        match &mut self.slots[index] {
+            Slot::Occupied { element, .. } => element,
+            Slot::Vacant { .. } => unreachable!("list links to a vacant slot"),
+        }
+    }
+
+    fn next(&self, index: usize) -> Link {
+        match &self.slots[index] {
+            Slot::Occupied { next, .. } => *next,
+            Slot::Vacant { .. } => unreachable!("list links to a vacant slot"),
+        }
+    }
+
+    fn set_next(&mut self, index: usize, link: Link) {
+        match &mut self.slots[index] {
+            Slot::Occupied { next, .. } => *next = link,
+            Slot::Vacant { .. } => unreachable!("list links to a vacant slot"),
+        }
+    }
+}
+
+// Dropping the arena drops each element in turn without recursing, so
+// unlike LinkedList no Drop impl is needed for long lists
+
+impl<T> Default for ArenaList<T> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ArenaList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self).finish()
+    }
+}
+
+// Cloning compacts the list into a fresh arena, in order, rather than
+// copying its vacant slots
+impl<T: Clone> Clone for ArenaList<T> {
+    fn clone(&self) -> Self {
+        let mut list = Self::with_capacity(self.len);
+        list.extend(self.iter().cloned());
+        list
+    }
+}
+
+impl<T: PartialEq> PartialEq for ArenaList<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other)
+    }
+}
+
+impl<T: Eq> Eq for ArenaList<T> {}
+
+impl<T: Hash> Hash for ArenaList<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len.hash(state);
+        for element in self {
+            element.hash(state);
+        }
+    }
+}
+
+impl<T: PartialOrd> PartialOrd for ArenaList<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other)
+    }
+}
+
+impl<T: Ord> Ord for ArenaList<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other)
+    }
+}
+
+impl<T> FromIterator<T> for ArenaList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = Self::empty();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<T> Extend<T> for ArenaList<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for element in iter {
+            self.push_back(element);
+        }
+    }
+}
+
+impl<'a, T: Copy + 'a> Extend<&'a T> for ArenaList<T> {
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+
+/// An iterator that moves elements out of a list, from the head.
+pub struct IntoIter<T>(ArenaList<T>);
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.pop()
+    }
+}
+
+impl<T> IntoIterator for ArenaList<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self)
+    }
+}
+
+/// An iterator over references to the elements of a list.
+pub struct Iter<'a, T> {
+    slots: &'a [Slot<T>],
+    next: Link,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.next?;
+        match &self.slots[index] {
+            Slot::Occupied { element, next } => {
+                self.next = *next;
+                Some(element)
+            }
+            Slot::Vacant { .. } => unreachable!("list links to a vacant slot"),
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a ArenaList<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over mutable references to the elements of a list.
+pub struct IterMut<'a, T> {
+    // Slots are visited in list order rather than arena order, which a
+    // borrowed slice can't hand out mutable references in
+    slots: *mut Slot<T>,
+    next: Link,
+    marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.next?;
+        // SAFETY: the list is mutably borrowed for 'a, so the arena can't
+        // move or change, and every link is in bounds. Each slot is linked
+        // to once, so each element is handed out only once
+        match unsafe { &mut *self.slots.add(index) } {
+            Slot::Occupied { element, next } => {
+                self.next = *next;
+                Some(element)
+            }
+            Slot::Vacant { .. } => unreachable!("list links to a vacant slot"),
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut ArenaList<T> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// An iterator that removes the elements of a list that match a
+/// predicate, made by [`ArenaList::extract_if`].
+pub struct ExtractIf<'a, T, P> {
+    cursor: CursorMut<'a, T>,
+    predicate: P,
+}
+
+impl<'a, T, P: FnMut(&mut T) -> bool> Iterator for ExtractIf<'a, T, P> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(element) = self.cursor.current() {
+            if (self.predicate)(element) {
+                return self.cursor.remove_current();
+            }
+            self.cursor.move_next();
+        }
+        None
+    }
+}
+
+/// A cursor over a list, positioned either at an element or at the end of
+/// the list, after the last element, as with
+/// [`LinkedList::cursor_front_mut`](crate::LinkedList::cursor_front_mut).
+///
+/// Moving, peeking and editing around the current element are O(1), while
+/// splicing in another list is O(n) in its length, as its elements move
+/// into this list's arena.
+pub struct CursorMut<'a, T> {
+    // The slot before the current one, whose link holds the current slot,
+    // or None when that is the list's head link
+    prev: Link,
+    list: &'a mut ArenaList<T>,
+}
+
+impl<'a, T> CursorMut<'a, T> {
+    fn current_link(&self) -> Link {
+        match self.prev {
+            Some(prev) => self.list.next(prev),
+            None => self.list.head,
+        }
+    }
+
+    fn set_current_link(&mut self, link: Link) {
+        match self.prev {
+            Some(prev) => self.list.set_next(prev, link),
+            None => self.list.head = link,
+        }
+    }
+
+    pub fn current(&mut self) -> Option<&mut T> {
+        let current = self.current_link()?;
+        Some(self.list.element_mut(current))
+    }
+
+    pub fn peek_next(&mut self) -> Option<&mut T> {
+        let next = self.list.next(self.current_link()?)?;
+        Some(self.list.element_mut(next))
+    }
+
+    /// Move to the next element, or to the end of the list after the last
+    /// one.
+    pub fn move_next(&mut self) {
+        if let Some(current) = self.current_link() {
+            self.prev = Some(current);
+        }
+    }
+
+    /// Insert an element before the current one, leaving the cursor where
+    /// it is. At the end of the list this appends to it.
+    pub fn insert_before(&mut self, element: T) {
+        let current = self.current_link();
+        let index = self.list.alloc(element, current);
+        self.set_current_link(Some(index));
+        if current.is_none() {
+            self.list.tail = Some(index);
+        }
+        self.prev = Some(index);
+    }
+
+    /// Insert an element after the current one. At the end of the list
+    /// this appends to it, like [`CursorMut::insert_before`].
+    pub fn insert_after(&mut self, element: T) {
+        match self.current_link() {
+            Some(current) => {
+                self.link_after(current, element);
+            }
+            None => self.insert_before(element),
+        }
+    }
+
+    /// Remove the current element, moving the cursor on to the next one.
+    pub fn remove_current(&mut self) -> Option<T> {
+        let current = self.current_link()?;
+        let (element, next) = self.list.release(current);
+        self.set_current_link(next);
+        if next.is_none() {
+            self.list.tail = self.prev;
+        }
+        Some(element)
+    }
+
+    /// Move every element of `list` in before the current one, leaving the
+    /// cursor where it is.
+    pub fn splice_before(&mut self, list: ArenaList<T>) {
+        for element in list {
+            self.insert_before(element);
+        }
+    }
+
+    /// Move every element of `list` in after the current one. At the end of
+    /// the list this appends them to it.
+    pub fn splice_after(&mut self, list: ArenaList<T>) {
+        let mut last = match self.current_link() {
+            Some(current) => current,
+            None => return self.splice_before(list),
+        };
+        for element in list {
+            last = self.link_after(last, element);
+        }
+    }
+
+    // Put an element in a new slot linked in after the given one, returning
+    // the new slot
+    fn link_after(&mut self, index: usize, element: T) -> usize {
+        let new = self.list.alloc(element, self.list.next(index));
+        self.list.set_next(index, Some(new));
+        if self.list.tail == Some(index) {
+            self.list.tail = Some(new);
+        }
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Collect a list's elements, checking along the way that its tail,
+    // length and free list all agree with its slots
+    fn contents<T: Copy>(list: &ArenaList<T>) -> Vec<T> {
+        let elements: Vec<T> = list.iter().copied().collect();
+        assert_eq!(list.len(), elements.len());
+        match list.tail {
+            Some(tail) => assert_eq!(list.next(tail), None),
+            None => assert!(list.head.is_none()),
+        }
+        let mut free = 0;
+        let mut link = list.free;
+        while let Some(i) = link {
+            free += 1;
+            link = match list.slots[i] {
+                Slot::Vacant { next_free } => next_free,
+                Slot::Occupied { .. } => panic!("free list holds an occupied slot"),
+            };
+        }
+        assert_eq!(free, list.vacant());
+        elements
+    }
+
+    #[test]
+    fn push_and_pop() {
+        let mut list = ArenaList::empty();
+        assert_eq!(list.pop(), None);
+        list.push(2);
+        list.push(1);
+        list.push_back(3);
+        assert_eq!(list.peek(), Some(&1));
+        assert_eq!(contents(&list), vec![1, 2, 3]);
+
+        assert_eq!(list.pop(), Some(1));
+        assert_eq!(list.pop(), Some(2));
+        assert_eq!(list.pop(), Some(3));
+        assert_eq!(list.pop(), None);
+        assert!(list.is_empty());
+        assert_eq!(ArenaList::new('x').peek(), Some(&'x'));
+    }
+
+    #[test]
+    fn peek_back() {
+        let mut list = ArenaList::empty();
+        assert_eq!(list.peek_back(), None);
+        assert_eq!(list.peek_back_mut(), None);
+        list.push(1);
+        assert_eq!(list.peek_back(), Some(&1));
+        list.push(0);
+        list.push_back(2);
+        *list.peek_back_mut().unwrap() *= 10;
+        assert_eq!(list.peek_back(), Some(&20));
+        assert_eq!(contents(&list), vec![0, 1, 20]);
+
+        // Popping down to one element leaves it at both ends
+        list.pop();
+        list.pop();
+        assert_eq!(list.peek_back(), Some(&20));
+        list.pop();
+        assert_eq!(list.peek_back(), None);
+    }
+
+    #[test]
+    fn reuses_freed_slots() {
+        let mut list = ArenaList::empty();
+        for i in 0..100 {
+            list.push(i);
+        }
+        let slots = list.slots.len();
+        for round in 0..10 {
+            while list.pop().is_some() {}
+            for i in 0..100 {
+                list.push_back(round * 100 + i);
+            }
+        }
+        assert_eq!(list.slots.len(), slots);
+        assert_eq!(contents(&list), (900..1000).collect::<Vec<_>>());
+
+        list.clear();
+        assert!(list.capacity() >= 100);
+        assert!(contents(&list).is_empty());
+    }
+
+    #[test]
+    fn query_and_edit() {
+        let mut list: ArenaList<_> = (1..=6).collect();
+        assert_eq!(list.get(2), Some(&3));
+        assert_eq!(list.get(6), None);
+        assert!(list.contains(&6));
+        assert_eq!(list.position(|n| *n > 4), Some(4));
+
+        *list.peek_mut().unwrap() = 10;
+        *list.get_mut(5).unwrap() = 60;
+        for n in list.iter_mut() {
+            *n += 1;
+        }
+        assert_eq!(contents(&list), vec![11, 3, 4, 5, 6, 61]);
+
+        let extracted: Vec<_> = list.extract_if(|n| *n % 2 == 1).collect();
+        assert_eq!(extracted, vec![11, 3, 5, 61]);
+        assert_eq!(contents(&list), vec![4, 6]);
+
+        list.retain(|n| *n != 4);
+        list.push_back(8);
+        assert_eq!(contents(&list), vec![6, 8]);
+    }
+
+    #[test]
+    fn reverse_append_and_split() {
+        let mut list: ArenaList<_> = (0..5).collect();
+        list.reverse();
+        assert_eq!(contents(&list), vec![4, 3, 2, 1, 0]);
+
+        let mut other: ArenaList<_> = (5..7).collect();
+        list.append(&mut other);
+        assert!(other.is_empty());
+        assert_eq!(contents(&list), vec![4, 3, 2, 1, 0, 5, 6]);
+
+        let split = list.split_off(3);
+        assert_eq!(contents(&list), vec![4, 3, 2]);
+        assert_eq!(contents(&split), vec![1, 0, 5, 6]);
+        list.push_back(9);
+        assert_eq!(contents(&list), vec![4, 3, 2, 9]);
+        assert!(contents(&list.split_off(4)).is_empty());
+    }
+
+    #[test]
+    fn sort_is_stable() {
+        let mut list: ArenaList<_> = vec![(3, 'a'), (1, 'b'), (3, 'c'), (2, 'd'), (1, 'e')]
+            .into_iter()
+            .collect();
+        list.sort_by_key(|pair| pair.0);
+        assert_eq!(
+            contents(&list),
+            vec![(1, 'b'), (1, 'e'), (2, 'd'), (3, 'a'), (3, 'c')]
+        );
+    }
+
+    #[test]
+    fn cursor_walks_and_edits() {
+        let mut list: ArenaList<_> = vec![1, 2, 3].into_iter().collect();
+        let mut cursor = list.cursor_front_mut();
+        assert_eq!(cursor.current(), Some(&mut 1));
+        assert_eq!(cursor.peek_next(), Some(&mut 2));
+
+        cursor.insert_before(0);
+        cursor.insert_after(10);
+        assert_eq!(cursor.current(), Some(&mut 1));
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&mut 10));
+
+        cursor.move_next();
+        assert_eq!(cursor.remove_current(), Some(2));
+        assert_eq!(cursor.current(), Some(&mut 3));
+        *cursor.current().unwrap() = 30;
+
+        cursor.move_next();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_next(), None);
+        assert_eq!(cursor.remove_current(), None);
+        cursor.insert_after(40);
+        cursor.insert_before(50);
+        assert_eq!(contents(&list), vec![0, 1, 10, 30, 40, 50]);
+
+        // Removing the last element moves the tail back, and its slot is
+        // reused by the next insertion
+        let mut cursor = list.cursor_front_mut();
+        for _ in 0..5 {
+            cursor.move_next();
+        }
+        assert_eq!(cursor.remove_current(), Some(50));
+        cursor.insert_after(60);
+        assert_eq!(contents(&list), vec![0, 1, 10, 30, 40, 60]);
+        assert_eq!(list.slots.len(), 6);
+    }
+
+    #[test]
+    fn cursor_splices() {
+        let mut list: ArenaList<_> = vec![1, 5].into_iter().collect();
+        let mut cursor = list.cursor_front_mut();
+        cursor.splice_after(vec![2, 3].into_iter().collect());
+        cursor.splice_before(vec![0].into_iter().collect());
+        cursor.splice_after(ArenaList::empty());
+        assert_eq!(cursor.current(), Some(&mut 1));
+
+        cursor.move_next();
+        cursor.move_next();
+        cursor.move_next();
+        cursor.splice_before(vec![4].into_iter().collect());
+        assert_eq!(cursor.current(), Some(&mut 5));
+        cursor.splice_after(vec![6, 7].into_iter().collect());
+        cursor.move_next();
+        cursor.move_next();
+        cursor.move_next();
+        cursor.splice_after(vec![8].into_iter().collect());
+        assert_eq!(cursor.current(), None);
+
+        assert_eq!(contents(&list), vec![0, 1, 2, 3, 4, 5, 6, 7, 8]);
+        list.push_back(9);
+        assert_eq!(list.iter().last(), Some(&9));
+    }
+
+    #[test]
+    fn traits_match_linked_list() {
+        let list: ArenaList<_> = vec![1, 2, 3].into_iter().collect();
+        let linked: crate::LinkedList<_> = vec![1, 2, 3].into_iter().collect();
+        assert_eq!(format!("{:?}", list), format!("{:?}", linked));
+
+        let mut clone = list.clone();
+        assert_eq!(clone, list);
+        clone.push(0);
+        assert!(clone < list);
+        assert_eq!(clone.into_iter().collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+    }
+}
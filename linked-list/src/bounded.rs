@@ -0,0 +1,224 @@
+//! A list that holds at most a fixed number of elements, for keeping a
+//! history of limited depth.
+//!
+//! Elements are kept in a [`LinkedList`] in the order they were pushed,
+//! with the oldest at the head, where it can be evicted in O(1) when the
+//! list is full, and the newest at the tail, where new elements are added.
+
+use crate::{IntoIter, Iter, LinkedList};
+
+/// The capacity of a [`BoundedList`] made with `default`.
+pub const DEFAULT_CAPACITY: usize = 16;
+
+/// What to do when pushing to a full list.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Overflow {
+    /// Refuse the new element, handing it back
+    Reject,
+    /// Make room by removing the oldest element
+    #[default]
+    EvictOldest,
+}
+
+#[derive(Debug)]
+pub struct BoundedList<T> {
+    elements: LinkedList<T>,
+    capacity: usize,
+    overflow: Overflow,
+}
+
+impl<T> BoundedList<T> {
+    /// An empty list holding at most `capacity` elements. A capacity of 0
+    /// is allowed, though nothing then fits: see [`BoundedList::push`].
+    pub fn new(capacity: usize, overflow: Overflow) -> Self {
+        Self {
+            elements: LinkedList::empty(),
+            capacity,
+            overflow,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.elements.len() >= self.capacity
+    }
+
+    /// Push a new element, following the overflow policy if the list is
+    /// full. Returns the element that was evicted to make room, if any, or
+    /// gives the new element back as an error if it was rejected.
+    ///
+    /// With a capacity of 0 the list is always full and holds nothing, so
+    /// when evicting, the new element is itself the oldest, and is handed
+    /// straight back as the evicted one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use linked_list::bounded::{BoundedList, Overflow};
+    ///
+    /// let mut history = BoundedList::new(2, Overflow::EvictOldest);
+    /// assert_eq!(history.push("a"), Ok(None));
+    /// assert_eq!(history.push("b"), Ok(None));
+    /// assert_eq!(history.push("c"), Ok(Some("a")));
+    ///
+    /// let mut queue = BoundedList::new(1, Overflow::Reject);
+    /// assert_eq!(queue.push(1), Ok(None));
+    /// assert_eq!(queue.push(2), Err(2));
+    /// ```
+    pub fn push(&mut self, element: T) -> Result<Option<T>, T> {
+        if !self.is_full() {
+            self.elements.push_back(element);
+            return Ok(None);
+        }
+
+        match self.overflow {
+            Overflow::Reject => Err(element),
+            Overflow::EvictOldest => {
+                self.elements.push_back(element);
+                Ok(self.elements.pop())
+            }
+        }
+    }
+
+    /// Remove the newest element. The list is singly linked, so this walks
+    /// it to find the element before the newest, in O(n).
+    pub fn pop(&mut self) -> Option<T> {
+        let len = self.elements.len();
+        if len == 0 {
+            return None;
+        }
+        self.elements.split_off(len - 1).pop()
+    }
+
+    /// Remove the oldest element.
+    pub fn pop_oldest(&mut self) -> Option<T> {
+        self.elements.pop()
+    }
+
+    /// The newest element.
+    pub fn peek(&self) -> Option<&T> {
+        self.elements.peek_back()
+    }
+
+    /// The oldest element.
+    pub fn peek_oldest(&self) -> Option<&T> {
+        self.elements.peek()
+    }
+
+    /// Iterate over the elements from oldest to newest.
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.elements.iter()
+    }
+}
+
+impl<T> Default for BoundedList<T> {
+    /// An empty list of [`DEFAULT_CAPACITY`] that evicts its oldest element
+    /// when full.
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY, Overflow::default())
+    }
+}
+
+impl<T> IntoIterator for BoundedList<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    /// Move the elements out, from oldest to newest.
+    fn into_iter(self) -> Self::IntoIter {
+        self.elements.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a BoundedList<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_oldest_when_full() {
+        let mut list = BoundedList::new(3, Overflow::EvictOldest);
+        for i in 0..3 {
+            assert_eq!(list.push(i), Ok(None));
+        }
+        assert!(list.is_full());
+        assert_eq!(list.push(3), Ok(Some(0)));
+        assert_eq!(list.push(4), Ok(Some(1)));
+
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.peek(), Some(&4));
+        assert_eq!(list.peek_oldest(), Some(&2));
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn rejects_when_full() {
+        let mut list = BoundedList::new(2, Overflow::Reject);
+        assert_eq!(list.push('a'), Ok(None));
+        assert_eq!(list.push('b'), Ok(None));
+        assert_eq!(list.push('c'), Err('c'));
+        assert_eq!(list.iter().collect::<String>(), "ab");
+
+        // Popping makes room again
+        assert_eq!(list.pop(), Some('b'));
+        assert_eq!(list.peek(), Some(&'a'));
+        assert_eq!(list.push('d'), Ok(None));
+        assert_eq!(list.pop_oldest(), Some('a'));
+        assert_eq!(list.pop(), Some('d'));
+        assert_eq!(list.pop(), None);
+        assert!(list.is_empty());
+        assert_eq!(list.peek(), None);
+    }
+
+    #[test]
+    fn zero_capacity() {
+        let mut reject = BoundedList::new(0, Overflow::Reject);
+        assert_eq!(reject.push(1), Err(1));
+
+        // The element pushed is the oldest, so it is the one evicted
+        let mut evict = BoundedList::new(0, Overflow::EvictOldest);
+        assert_eq!(evict.push(1), Ok(Some(1)));
+        assert!(evict.is_empty());
+        assert_eq!(evict.capacity(), 0);
+    }
+
+    #[test]
+    fn default_debug_and_iteration() {
+        let mut list = BoundedList::default();
+        assert_eq!(list.capacity(), DEFAULT_CAPACITY);
+        for i in 0..20 {
+            list.push(i).unwrap();
+        }
+        assert_eq!(list.peek_oldest(), Some(&4));
+
+        let mut short = BoundedList::new(2, Overflow::Reject);
+        short.push("a").unwrap();
+        assert_eq!(
+            format!("{:?}", short),
+            r#"BoundedList { elements: ["a"], capacity: 2, overflow: Reject }"#
+        );
+
+        let borrowed: Vec<_> = (&list).into_iter().copied().collect();
+        let owned: Vec<_> = list.into_iter().collect();
+        assert_eq!(owned, (4..20).collect::<Vec<_>>());
+        assert_eq!(borrowed, owned);
+    }
+}
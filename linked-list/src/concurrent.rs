@@ -0,0 +1,195 @@
+//! A lock-free stack that many threads can push to and pop from at once.
+//!
+//! This is a Treiber stack: the same chain of nodes as
+//! [`LinkedList`](crate::LinkedList), but with the head held in an atomic
+//! pointer that is only ever changed by a compare-and-swap. A thread that
+//! loses a race to another just reads the new head and tries again.
+//!
+//! The hard part is freeing popped nodes, since another thread may still be
+//! looking at one it read as the head a moment ago. Nodes are reclaimed
+//! with `crossbeam-epoch`, which only frees a node once every thread that
+//! could have seen it has moved on. That also rules out the ABA problem,
+//! where a node is freed and its address reused for a new head while a
+//! compare-and-swap still expects the old one.
+
+use crossbeam_epoch::{self as epoch, Atomic, Owned};
+use std::mem::ManuallyDrop;
+use std::ptr;
+use std::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+
+pub struct Stack<T> {
+    head: Atomic<Node<T>>,
+}
+
+struct Node<T> {
+    // Popping moves the element out while the node itself may still be
+    // read by other threads, so the node must never drop it
+    element: ManuallyDrop<T>,
+    next: Atomic<Node<T>>,
+}
+
+impl<T> Stack<T> {
+    pub fn new() -> Self {
+        Self {
+            head: Atomic::null(),
+        }
+    }
+
+    pub fn push(&self, element: T) {
+        let mut node = Owned::new(Node {
+            element: ManuallyDrop::new(element),
+            next: Atomic::null(),
+        });
+        let guard = epoch::pin();
+
+        loop {
+            let head = self.head.load(Relaxed, &guard);
+            node.next.store(head, Relaxed);
+
+            // Release makes the node's contents visible to whichever thread
+            // pops it
+            match self
+                .head
+                .compare_exchange(head, node, Release, Relaxed, &guard)
+            {
+                Ok(_) => return,
+                Err(e) => node = e.new,
+            }
+        }
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let guard = epoch::pin();
+
+        loop {
+            let head = self.head.load(Acquire, &guard);
+            // SAFETY: the guard keeps the node from being freed while it's
+            // pinned, even if another thread pops it first
+            let node = unsafe { head.as_ref() }?;
+            let next = node.next.load(Relaxed, &guard);
+
+            if self
+                .head
+                .compare_exchange(head, next, Relaxed, Relaxed, &guard)
+                .is_ok()
+            {
+                // SAFETY: winning the compare-and-swap means this thread
+                // alone unlinked the node, so it alone takes the element and
+                // schedules the node to be freed once no thread can see it
+                unsafe {
+                    guard.defer_destroy(head);
+                    return Some(ManuallyDrop::into_inner(ptr::read(&node.element)));
+                }
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let guard = epoch::pin();
+        self.head.load(Acquire, &guard).is_null()
+    }
+}
+
+impl<T> Drop for Stack<T> {
+    fn drop(&mut self) {
+        // SAFETY: dropping takes the stack by &mut, so no other thread can
+        // be looking at its nodes, and they can be freed straight away
+        unsafe {
+            let guard = epoch::unprotected();
+            let mut link = self.head.load(Relaxed, guard);
+            while let Some(node) = link.as_ref() {
+                let next = node.next.load(Relaxed, guard);
+                let mut node = link.into_owned();
+                ManuallyDrop::drop(&mut node.element);
+                link = next;
+            }
+        }
+    }
+}
+
+impl<T> Default for Stack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// SAFETY: elements are moved in by one thread and out by another, but never
+// shared, so sending elements between threads is all that's needed
+unsafe impl<T: Send> Send for Stack<T> {}
+unsafe impl<T: Send> Sync for Stack<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn push_and_pop() {
+        let stack = Stack::new();
+        assert!(stack.is_empty());
+        assert_eq!(stack.pop(), None);
+
+        stack.push(1);
+        stack.push(2);
+        assert!(!stack.is_empty());
+        assert_eq!(stack.pop(), Some(2));
+        assert_eq!(stack.pop(), Some(1));
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    fn drops_remaining_elements() {
+        let counted = Arc::new(());
+        let stack = Stack::new();
+        for _ in 0..10 {
+            stack.push(Arc::clone(&counted));
+        }
+        stack.pop();
+        drop(stack);
+        assert_eq!(Arc::strong_count(&counted), 1);
+    }
+
+    #[test]
+    fn many_producers_and_consumers() {
+        const THREADS: usize = 8;
+        let per_thread = if cfg!(miri) { 50 } else { 20_000 };
+
+        let stack = Stack::new();
+        let popped = AtomicUsize::new(0);
+        let sum = AtomicUsize::new(0);
+
+        thread::scope(|s| {
+            for t in 0..THREADS {
+                let (stack, popped, sum) = (&stack, &popped, &sum);
+                s.spawn(move || {
+                    for i in 0..per_thread {
+                        stack.push(t * per_thread + i);
+                        // Interleave pops with pushes so that threads race
+                        // over the same nodes
+                        if i % 2 == 1 {
+                            while let Some(n) = stack.pop() {
+                                popped.fetch_add(1, Relaxed);
+                                sum.fetch_add(n, Relaxed);
+                                if n % 3 == 0 {
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        while let Some(n) = stack.pop() {
+            popped.fetch_add(1, Relaxed);
+            sum.fetch_add(n, Relaxed);
+        }
+
+        // Every element came out exactly once
+        let total = THREADS * per_thread;
+        assert_eq!(popped.into_inner(), total);
+        assert_eq!(sum.into_inner(), total * (total - 1) / 2);
+    }
+}
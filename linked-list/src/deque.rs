@@ -0,0 +1,743 @@
+//! A doubly linked deque, with O(1) pushes, pops and peeks at both ends.
+//!
+//! Each node points at both of its neighbours, which safe Rust can't
+//! express with `Box` alone, since a node would need two owners. Instead
+//! the deque owns every node through raw pointers made with `Box::leak`,
+//! and turns each back into a `Box` exactly once, when it is popped or the
+//! deque is dropped. The unsafe blocks below rely on these invariants:
+//!
+//! - `head` and `tail` are both `None` or both `Some`, and `len` counts the
+//!   nodes between them
+//! - every pointer in the deque points at a live node that it owns
+//! - a node's `next` has that node as its `prev`, and vice versa
+//!
+//! The tests are small enough to run under Miri, with
+//! `cargo +nightly miri test -p linked-list`.
+
+use std::fmt;
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+pub struct Deque<T> {
+    head: Link<T>,
+    tail: Link<T>,
+    len: usize,
+    // Tells the compiler that the deque owns nodes of T, for drop checking
+    // and variance, even though it only holds pointers to them
+    marker: PhantomData<Box<Node<T>>>,
+}
+
+struct Node<T> {
+    element: T,
+    prev: Link<T>,
+    next: Link<T>,
+}
+
+type Link<T> = Option<NonNull<Node<T>>>;
+
+impl<T> Deque<T> {
+    pub fn new() -> Self {
+        Self {
+            head: None,
+            tail: None,
+            len: 0,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push_front(&mut self, element: T) {
+        let node = Box::new(Node {
+            element,
+            prev: None,
+            next: self.head,
+        });
+        let node = NonNull::from(Box::leak(node));
+
+        match self.head {
+            // SAFETY: the old head is a live node owned by the deque
+            Some(head) => unsafe { (*head.as_ptr()).prev = Some(node) },
+            None => self.tail = Some(node),
+        }
+        self.head = Some(node);
+        self.len += 1;
+    }
+
+    pub fn push_back(&mut self, element: T) {
+        let node = Box::new(Node {
+            element,
+            prev: self.tail,
+            next: None,
+        });
+        let node = NonNull::from(Box::leak(node));
+
+        match self.tail {
+            // SAFETY: the old tail is a live node owned by the deque
+            Some(tail) => unsafe { (*tail.as_ptr()).next = Some(node) },
+            None => self.head = Some(node),
+        }
+        self.tail = Some(node);
+        self.len += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.head.map(|head| {
+            // SAFETY: the head was leaked from a Box by a push, and is
+            // unlinked here so that nothing else can reach it
+            let node = unsafe { Box::from_raw(head.as_ptr()) };
+            self.head = node.next;
+            match self.head {
+                // SAFETY: the new head is a live node owned by the deque
+                Some(head) => unsafe { (*head.as_ptr()).prev = None },
+                None => self.tail = None,
+            }
+            self.len -= 1;
+            node.element
+        })
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        self.tail.map(|tail| {
+            // SAFETY: as in pop_front
+            let node = unsafe { Box::from_raw(tail.as_ptr()) };
+            self.tail = node.prev;
+            match self.tail {
+                // SAFETY: the new tail is a live node owned by the deque
+                Some(tail) => unsafe { (*tail.as_ptr()).next = None },
+                None => self.head = None,
+            }
+            self.len -= 1;
+            node.element
+        })
+    }
+
+    pub fn peek_front(&self) -> Option<&T> {
+        // SAFETY: the node lives as long as the deque, and the shared
+        // borrow of the deque stops it being popped or changed
+        self.head.map(|node| unsafe { &(*node.as_ptr()).element })
+    }
+
+    pub fn peek_back(&self) -> Option<&T> {
+        // SAFETY: as in peek_front
+        self.tail.map(|node| unsafe { &(*node.as_ptr()).element })
+    }
+
+    pub fn peek_front_mut(&mut self) -> Option<&mut T> {
+        // SAFETY: the mutable borrow of the deque makes this the only
+        // reference to the node
+        self.head
+            .map(|node| unsafe { &mut (*node.as_ptr()).element })
+    }
+
+    pub fn peek_back_mut(&mut self) -> Option<&mut T> {
+        // SAFETY: as in peek_front_mut
+        self.tail
+            .map(|node| unsafe { &mut (*node.as_ptr()).element })
+    }
+
+    /// A cursor starting at the front element.
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            current: self.head,
+            index: 0,
+            deque: self,
+        }
+    }
+
+    /// A cursor starting at the back element.
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            current: self.tail,
+            index: self.len.saturating_sub(1),
+            deque: self,
+        }
+    }
+
+    /// Link the chain of `count` nodes from `first` to `last` in between
+    /// `prev` and `next`, where `None` stands for the ends of the deque.
+    ///
+    /// # Safety
+    ///
+    /// `prev` and `next` must be adjacent in this deque, and the chain must
+    /// be owned by nothing else.
+    unsafe fn link_between(
+        &mut self,
+        prev: Link<T>,
+        next: Link<T>,
+        first: NonNull<Node<T>>,
+        last: NonNull<Node<T>>,
+        count: usize,
+    ) {
+        (*first.as_ptr()).prev = prev;
+        (*last.as_ptr()).next = next;
+        match prev {
+            Some(prev) => (*prev.as_ptr()).next = Some(first),
+            None => self.head = Some(first),
+        }
+        match next {
+            Some(next) => (*next.as_ptr()).prev = Some(last),
+            None => self.tail = Some(last),
+        }
+        self.len += count;
+    }
+
+    /// Iterate over references to the elements, from front to back.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            front: self.head,
+            back: self.tail,
+            len: self.len,
+            marker: PhantomData,
+        }
+    }
+
+    /// Iterate over mutable references to the elements, from front to back.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            front: self.head,
+            back: self.tail,
+            len: self.len,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Drop for Deque<T> {
+    fn drop(&mut self) {
+        // Popping frees one node at a time, without recursion
+        while self.pop_front().is_some() {}
+    }
+}
+
+impl<T> Default for Deque<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Deque<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self).finish()
+    }
+}
+
+impl<T> FromIterator<T> for Deque<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut deque = Self::new();
+        deque.extend(iter);
+        deque
+    }
+}
+
+impl<T> Extend<T> for Deque<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for element in iter {
+            self.push_back(element);
+        }
+    }
+}
+
+/// A cursor over a deque, positioned either at an element or at a "ghost"
+/// position between the back and the front, as in `std`'s cursors. Moving
+/// past either end lands on the ghost, and moving on from there wraps
+/// around. Every operation is O(1), including splicing in another deque.
+pub struct CursorMut<'a, T> {
+    current: Link<T>,
+    // The index of the current element, or the length of the deque at the
+    // ghost
+    index: usize,
+    deque: &'a mut Deque<T>,
+}
+
+impl<'a, T> CursorMut<'a, T> {
+    /// The index of the current element, or `None` at the ghost.
+    pub fn index(&self) -> Option<usize> {
+        self.current.map(|_| self.index)
+    }
+
+    pub fn current(&mut self) -> Option<&mut T> {
+        // SAFETY: the cursor borrows the deque mutably, and this borrows the
+        // cursor mutably, so this is the only reference to the element
+        self.current
+            .map(|node| unsafe { &mut (*node.as_ptr()).element })
+    }
+
+    pub fn peek_next(&mut self) -> Option<&mut T> {
+        // SAFETY: as in current
+        self.next_link()
+            .map(|node| unsafe { &mut (*node.as_ptr()).element })
+    }
+
+    pub fn peek_prev(&mut self) -> Option<&mut T> {
+        // SAFETY: as in current
+        self.prev_link()
+            .map(|node| unsafe { &mut (*node.as_ptr()).element })
+    }
+
+    fn next_link(&self) -> Link<T> {
+        match self.current {
+            // SAFETY: the current node is live while the deque is borrowed
+            Some(node) => unsafe { (*node.as_ptr()).next },
+            None => self.deque.head,
+        }
+    }
+
+    fn prev_link(&self) -> Link<T> {
+        match self.current {
+            // SAFETY: as in next_link
+            Some(node) => unsafe { (*node.as_ptr()).prev },
+            None => self.deque.tail,
+        }
+    }
+
+    /// Move to the next element. From the back this moves to the ghost, and
+    /// from the ghost to the front.
+    pub fn move_next(&mut self) {
+        self.index = match self.current {
+            Some(_) => self.index + 1,
+            None => 0,
+        };
+        self.current = self.next_link();
+    }
+
+    /// Move to the previous element. From the front this moves to the
+    /// ghost, and from the ghost to the back.
+    pub fn move_prev(&mut self) {
+        self.current = self.prev_link();
+        self.index = match self.current {
+            Some(_) => self.index.checked_sub(1).unwrap_or(self.deque.len - 1),
+            None => self.deque.len,
+        };
+    }
+
+    /// Insert an element after the current one, or at the front from the
+    /// ghost.
+    pub fn insert_after(&mut self, element: T) {
+        let node = new_node(element);
+        let (prev, next) = (self.current, self.next_link());
+        // SAFETY: the current and next nodes are adjacent, and the new node
+        // was only just made
+        unsafe { self.deque.link_between(prev, next, node, node, 1) };
+        if self.current.is_none() {
+            self.index += 1;
+        }
+    }
+
+    /// Insert an element before the current one, or at the back from the
+    /// ghost.
+    pub fn insert_before(&mut self, element: T) {
+        let node = new_node(element);
+        let (prev, next) = (self.prev_link(), self.current);
+        // SAFETY: as in insert_after
+        unsafe { self.deque.link_between(prev, next, node, node, 1) };
+        self.index += 1;
+    }
+
+    /// Remove the current element, moving the cursor on to the next one, or
+    /// to the ghost if it was at the back.
+    pub fn remove_current(&mut self) -> Option<T> {
+        let current = self.current?;
+        let (prev, next) = (self.prev_link(), self.next_link());
+        // SAFETY: the node is unlinked from its neighbours here, after which
+        // nothing else refers to it, and it was leaked from a Box by a push
+        let node = unsafe {
+            match prev {
+                Some(prev) => (*prev.as_ptr()).next = next,
+                None => self.deque.head = next,
+            }
+            match next {
+                Some(next) => (*next.as_ptr()).prev = prev,
+                None => self.deque.tail = prev,
+            }
+            Box::from_raw(current.as_ptr())
+        };
+        self.deque.len -= 1;
+        self.current = next;
+        Some(node.element)
+    }
+
+    /// Move every element of `other` in after the current one, or at the
+    /// front from the ghost.
+    pub fn splice_after(&mut self, other: Deque<T>) {
+        let (prev, next) = (self.current, self.next_link());
+        if let Some(count) = self.splice(prev, next, other) {
+            if self.current.is_none() {
+                self.index += count;
+            }
+        }
+    }
+
+    /// Move every element of `other` in before the current one, or at the
+    /// back from the ghost.
+    pub fn splice_before(&mut self, other: Deque<T>) {
+        let (prev, next) = (self.prev_link(), self.current);
+        if let Some(count) = self.splice(prev, next, other) {
+            self.index += count;
+        }
+    }
+
+    fn splice(&mut self, prev: Link<T>, next: Link<T>, mut other: Deque<T>) -> Option<usize> {
+        let (first, last) = (other.head.take()?, other.tail.take()?);
+        let count = std::mem::replace(&mut other.len, 0);
+        // SAFETY: other has been emptied, so its nodes now belong to this
+        // deque alone
+        unsafe { self.deque.link_between(prev, next, first, last, count) };
+        Some(count)
+    }
+}
+
+fn new_node<T>(element: T) -> NonNull<Node<T>> {
+    NonNull::from(Box::leak(Box::new(Node {
+        element,
+        prev: None,
+        next: None,
+    })))
+}
+
+// SAFETY: the deque owns its elements just as a Vec<T> would, so it can be
+// sent or shared between threads whenever T can
+unsafe impl<T: Send> Send for Deque<T> {}
+unsafe impl<T: Sync> Sync for Deque<T> {}
+
+/// An iterator that moves elements out of a deque.
+pub struct IntoIter<T>(Deque<T>);
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len, Some(self.0.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.pop_back()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> IntoIterator for Deque<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self)
+    }
+}
+
+/// An iterator over references to the elements of a deque.
+pub struct Iter<'a, T> {
+    front: Link<T>,
+    back: Link<T>,
+    // Counting what's left stops the two ends passing each other
+    len: usize,
+    marker: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.front.map(|node| {
+            // SAFETY: the deque is borrowed for 'a, so its nodes live and
+            // stay unchanged that long
+            let node = unsafe { &*node.as_ptr() };
+            self.len -= 1;
+            self.front = node.next;
+            &node.element
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.back.map(|node| {
+            // SAFETY: as in next
+            let node = unsafe { &*node.as_ptr() };
+            self.len -= 1;
+            self.back = node.prev;
+            &node.element
+        })
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+impl<'a, T> IntoIterator for &'a Deque<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over mutable references to the elements of a deque.
+pub struct IterMut<'a, T> {
+    front: Link<T>,
+    back: Link<T>,
+    len: usize,
+    marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.front.map(|node| {
+            // SAFETY: the deque is mutably borrowed for 'a, and the length
+            // check means each element is handed out only once
+            let node = unsafe { &mut *node.as_ptr() };
+            self.len -= 1;
+            self.front = node.next;
+            &mut node.element
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for IterMut<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.back.map(|node| {
+            // SAFETY: as in next
+            let node = unsafe { &mut *node.as_ptr() };
+            self.len -= 1;
+            self.back = node.prev;
+            &mut node.element
+        })
+    }
+}
+
+impl<'a, T> ExactSizeIterator for IterMut<'a, T> {}
+
+impl<'a, T> IntoIterator for &'a mut Deque<T> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_and_pop_at_both_ends() {
+        let mut deque = Deque::new();
+        assert_eq!(deque.pop_front(), None);
+        assert_eq!(deque.pop_back(), None);
+
+        deque.push_back(2);
+        deque.push_front(1);
+        deque.push_back(3);
+        assert_eq!(deque.len(), 3);
+
+        assert_eq!(deque.pop_front(), Some(1));
+        assert_eq!(deque.pop_back(), Some(3));
+        assert_eq!(deque.pop_back(), Some(2));
+        assert_eq!(deque.pop_front(), None);
+        assert!(deque.is_empty());
+
+        // Emptying it shouldn't leave anything dangling
+        deque.push_front(4);
+        assert_eq!(deque.pop_back(), Some(4));
+        assert_eq!(deque.peek_front(), None);
+    }
+
+    #[test]
+    fn peek_at_both_ends() {
+        let mut deque = Deque::new();
+        assert_eq!(deque.peek_back(), None);
+        deque.push_back("a");
+        deque.push_back("b");
+        assert_eq!(deque.peek_front(), Some(&"a"));
+        assert_eq!(deque.peek_back(), Some(&"b"));
+
+        if let Some(back) = deque.peek_back_mut() {
+            *back = "c";
+        }
+        if let Some(front) = deque.peek_front_mut() {
+            *front = "z";
+        }
+        assert_eq!(deque.pop_back(), Some("c"));
+        assert_eq!(deque.pop_back(), Some("z"));
+    }
+
+    #[test]
+    fn iterate_from_both_ends() {
+        let mut deque: Deque<_> = (1..=5).collect();
+        assert_eq!(deque.iter().len(), 5);
+
+        let mut iter = deque.iter();
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next_back(), Some(&5));
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next_back(), Some(&4));
+        assert_eq!(iter.next(), Some(&3));
+        assert_eq!(iter.next_back(), None);
+        assert_eq!(iter.next(), None);
+
+        for element in deque.iter_mut().rev().take(2) {
+            *element *= 10;
+        }
+        assert_eq!(format!("{:?}", deque), "[1, 2, 3, 40, 50]");
+
+        let mut into_iter = deque.into_iter();
+        assert_eq!(into_iter.next_back(), Some(50));
+        assert_eq!(into_iter.collect::<Vec<_>>(), vec![1, 2, 3, 40]);
+    }
+
+    #[test]
+    fn drops_every_element() {
+        use std::rc::Rc;
+
+        let counted = Rc::new(());
+        let mut deque = Deque::new();
+        for _ in 0..10 {
+            deque.push_back(Rc::clone(&counted));
+        }
+        deque.pop_front();
+        deque.pop_back();
+        assert_eq!(Rc::strong_count(&counted), 9);
+
+        let mut into_iter = deque.into_iter();
+        into_iter.next();
+        drop(into_iter);
+        assert_eq!(Rc::strong_count(&counted), 1);
+    }
+
+    #[test]
+    fn cursor_moves_both_ways_through_the_ghost() {
+        let mut deque: Deque<_> = (1..=3).collect();
+        let mut cursor = deque.cursor_back_mut();
+        assert_eq!(cursor.index(), Some(2));
+        assert_eq!(cursor.peek_prev(), Some(&mut 2));
+
+        cursor.move_next();
+        assert_eq!(cursor.index(), None);
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_next(), Some(&mut 1));
+        assert_eq!(cursor.peek_prev(), Some(&mut 3));
+
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&mut 1));
+        assert_eq!(cursor.index(), Some(0));
+
+        cursor.move_prev();
+        assert_eq!(cursor.index(), None);
+        cursor.move_prev();
+        assert_eq!(cursor.current(), Some(&mut 3));
+        assert_eq!(cursor.index(), Some(2));
+
+        let mut empty: Deque<i32> = Deque::new();
+        let mut cursor = empty.cursor_front_mut();
+        cursor.move_next();
+        cursor.move_prev();
+        assert_eq!(cursor.index(), None);
+    }
+
+    #[test]
+    fn cursor_inserts_and_removes() {
+        let mut deque: Deque<_> = (1..=3).collect();
+        let mut cursor = deque.cursor_front_mut();
+        cursor.move_next();
+        cursor.insert_before(10);
+        cursor.insert_after(20);
+        assert_eq!(cursor.index(), Some(2));
+        assert_eq!(cursor.current(), Some(&mut 2));
+
+        assert_eq!(cursor.remove_current(), Some(2));
+        assert_eq!(cursor.current(), Some(&mut 20));
+        assert_eq!(cursor.index(), Some(2));
+
+        cursor.move_prev();
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.index(), None);
+        cursor.insert_after(0);
+        cursor.insert_before(99);
+        assert_eq!(cursor.index(), None);
+        assert_eq!(cursor.remove_current(), None);
+
+        cursor.move_prev();
+        assert_eq!(cursor.remove_current(), Some(99));
+        assert_eq!(cursor.index(), None);
+        assert_eq!(format!("{:?}", deque), "[0, 1, 10, 20, 3]");
+        assert_eq!(deque.len(), 5);
+        assert_eq!(deque.peek_back(), Some(&3));
+
+        let mut single: Deque<_> = Some(1).into_iter().collect();
+        assert_eq!(single.cursor_front_mut().remove_current(), Some(1));
+        assert!(single.is_empty());
+        assert_eq!(single.peek_front(), None);
+        assert_eq!(single.peek_back(), None);
+    }
+
+    #[test]
+    fn cursor_splices() {
+        let mut deque: Deque<_> = vec![1, 4].into_iter().collect();
+        let mut cursor = deque.cursor_front_mut();
+        cursor.splice_after(vec![2, 3].into_iter().collect());
+        cursor.splice_before(Deque::new());
+        cursor.splice_before(vec![0].into_iter().collect());
+        assert_eq!(cursor.index(), Some(1));
+
+        cursor.move_prev();
+        cursor.move_prev();
+        cursor.splice_before(vec![5].into_iter().collect());
+        cursor.splice_after(vec![-2, -1].into_iter().collect());
+        assert_eq!(cursor.index(), None);
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&mut -2));
+
+        assert_eq!(deque.len(), 8);
+        assert_eq!(
+            deque.iter().rev().copied().collect::<Vec<_>>(),
+            vec![5, 4, 3, 2, 1, 0, -1, -2]
+        );
+    }
+
+    #[test]
+    fn drop_long_deque() {
+        // Keep Miri runs short
+        let n = if cfg!(miri) { 1_000 } else { 1_000_000 };
+        let deque: Deque<_> = (0..n).collect();
+        assert_eq!(deque.len(), n);
+        drop(deque);
+    }
+}
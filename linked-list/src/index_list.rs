@@ -0,0 +1,366 @@
+//! A doubly linked list whose nodes live in a single growable arena, and
+//! which hands out a handle to each element it holds.
+//!
+//! Like [`ArenaList`](crate::ArenaList), nodes are kept in one `Vec` and
+//! linked by index, with freed slots reused by later insertions. Links run
+//! both ways, so that an element can be removed or moved to the front in
+//! O(1) given only its handle, which makes this the list to build caches
+//! and other structures that keep their own index into a list on.
+//!
+//! A handle stays valid until its element is removed. After that it may
+//! be reused for another element, so it shouldn't be kept around.
+
+use std::fmt;
+use std::iter::FromIterator;
+use std::mem;
+
+pub struct IndexList<T> {
+    slots: Vec<Slot<T>>,
+    head: Link,
+    tail: Link,
+    // The first of the vacant slots, each of which links to the next
+    free: Link,
+    len: usize,
+}
+
+type Link = Option<usize>;
+
+enum Slot<T> {
+    Occupied { element: T, prev: Link, next: Link },
+    Vacant { next_free: Link },
+}
+
+impl<T> IndexList<T> {
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            head: None,
+            tail: None,
+            free: None,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The handle of the first element.
+    pub fn front(&self) -> Option<usize> {
+        self.head
+    }
+
+    /// The handle of the last element.
+    pub fn back(&self) -> Option<usize> {
+        self.tail
+    }
+
+    /// Add an element to the front of the list, returning its handle.
+    pub fn push_front(&mut self, element: T) -> usize {
+        let index = self.alloc(element);
+        self.link_front(index);
+        index
+    }
+
+    /// Add an element to the back of the list, returning its handle.
+    pub fn push_back(&mut self, element: T) -> usize {
+        let index = self.alloc(element);
+        self.set_prev(index, self.tail);
+        match self.tail {
+            Some(tail) => self.set_next(tail, Some(index)),
+            None => self.head = Some(index),
+        }
+        self.tail = Some(index);
+        index
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.remove(self.head?)
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        self.remove(self.tail?)
+    }
+
+    pub fn get(&self, handle: usize) -> Option<&T> {
+        match self.slots.get(handle)? {
+            Slot::Occupied { element, .. } => Some(element),
+            Slot::Vacant { .. } => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, handle: usize) -> Option<&mut T> {
+        match self.slots.get_mut(handle)? {
+            Slot::Occupied { element, .. } => Some(element),
+            Slot::Vacant { .. } => None,
+        }
+    }
+
+    /// Remove the element with the given handle, in O(1).
+    pub fn remove(&mut self, handle: usize) -> Option<T> {
+        self.get(handle)?;
+        self.unlink(handle);
+        let vacant = Slot::Vacant {
+            next_free: self.free,
+        };
+        self.free = Some(handle);
+        self.len -= 1;
+        match mem::replace(&mut self.slots[handle], vacant) {
+            Slot::Occupied { element, .. } => Some(element),
+            Slot::Vacant { .. } => unreachable!("checked to be occupied"),
+        }
+    }
+
+    /// Move the element with the given handle to the front, in O(1). Its
+    /// handle stays the same.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use linked_list::IndexList;
+    ///
+    /// let mut list = IndexList::new();
+    /// let a = list.push_back("a");
+    /// let b = list.push_back("b");
+    /// list.move_to_front(b);
+    /// assert_eq!(list.iter().collect::<Vec<_>>(), vec![&"b", &"a"]);
+    /// assert_eq!(list.remove(a), Some("a"));
+    /// assert_eq!(list.get(a), None);
+    /// ```
+    pub fn move_to_front(&mut self, handle: usize) {
+        if self.get(handle).is_some() && self.head != Some(handle) {
+            self.unlink(handle);
+            self.link_front(handle);
+        }
+    }
+
+    /// Remove every element, keeping the arena's memory for reuse.
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.head = None;
+        self.tail = None;
+        self.free = None;
+        self.len = 0;
+    }
+
+    /// Iterate over references to the elements, from the front.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            slots: &self.slots,
+            next: self.head,
+        }
+    }
+
+    fn alloc(&mut self, element: T) -> usize {
+        let slot = Slot::Occupied {
+            element,
+            prev: None,
+            next: None,
+        };
+        self.len += 1;
+        match self.free {
+            Some(index) => {
+                match mem::replace(&mut self.slots[index], slot) {
+                    Slot::Vacant { next_free } => self.free = next_free,
+                    Slot::Occupied { .. } => unreachable!("free list holds an occupied slot"),
+                }
+                index
+            }
+            None => {
+                self.slots.push(slot);
+                self.slots.len() - 1
+            }
+        }
+    }
+
+    fn link_front(&mut self, index: usize) {
+        self.set_prev(index, None);
+        self.set_next(index, self.head);
+        match self.head {
+            Some(head) => self.set_prev(head, Some(index)),
+            None => self.tail = Some(index),
+        }
+        self.head = Some(index);
+    }
+
+    fn unlink(&mut self, index: usize) {
+        let (prev, next) = self.links(index);
+        match prev {
+            Some(prev) => self.set_next(prev, next),
+            None => self.head = next,
+        }
+        match next {
+            Some(next) => self.set_prev(next, prev),
+            None => self.tail = prev,
+        }
+    }
+
+    fn links(&self, index: usize) -> (Link, Link) {
+        match &self.slots[index] {
+            Slot::Occupied { prev, next, .. } => (*prev, *next),
+            Slot::Vacant { .. } => unreachable!("list links to a vacant slot"),
+        }
+    }
+
+    fn set_prev(&mut self, index: usize, link: Link) {
+        match &mut self.slots[index] {
+            Slot::Occupied { prev, .. } => *prev = link,
+            Slot::Vacant { .. } => unreachable!("list links to a vacant slot"),
+        }
+    }
+
+    fn set_next(&mut self, index: usize, link: Link) {
+        match &mut self.slots[index] {
+            Slot::Occupied { next, .. } => *next = link,
+            Slot::Vacant { .. } => unreachable!("list links to a vacant slot"),
+        }
+    }
+}
+
+impl<T> Default for IndexList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for IndexList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self).finish()
+    }
+}
+
+impl<T> FromIterator<T> for IndexList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = Self::new();
+        for element in iter {
+            list.push_back(element);
+        }
+        list
+    }
+}
+
+/// An iterator over references to the elements of a list.
+pub struct Iter<'a, T> {
+    slots: &'a [Slot<T>],
+    next: Link,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.next?;
+        match &self.slots[index] {
+            Slot::Occupied { element, next, .. } => {
+                self.next = *next;
+                Some(element)
+            }
+            Slot::Vacant { .. } => unreachable!("list links to a vacant slot"),
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a IndexList<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Collect a list's elements, checking along the way that the links
+    // agree in both directions, and with its length and free list
+    fn contents<T: Copy + PartialEq + fmt::Debug>(list: &IndexList<T>) -> Vec<T> {
+        let elements: Vec<T> = list.iter().copied().collect();
+        assert_eq!(list.len(), elements.len());
+
+        let mut backwards = Vec::new();
+        let mut link = list.tail;
+        while let Some(i) = link {
+            backwards.push(*list.get(i).unwrap());
+            link = list.links(i).0;
+        }
+        backwards.reverse();
+        assert_eq!(backwards, elements);
+
+        let mut free = 0;
+        let mut link = list.free;
+        while let Some(i) = link {
+            free += 1;
+            link = match list.slots[i] {
+                Slot::Vacant { next_free } => next_free,
+                Slot::Occupied { .. } => panic!("free list holds an occupied slot"),
+            };
+        }
+        assert_eq!(free, list.slots.len() - list.len());
+        elements
+    }
+
+    #[test]
+    fn push_pop_and_remove() {
+        let mut list = IndexList::new();
+        assert_eq!(list.pop_front(), None);
+        let b = list.push_front(2);
+        let a = list.push_front(1);
+        let c = list.push_back(3);
+        assert_eq!(contents(&list), vec![1, 2, 3]);
+        assert_eq!((list.front(), list.back()), (Some(a), Some(c)));
+
+        assert_eq!(list.remove(b), Some(2));
+        assert_eq!(list.remove(b), None);
+        assert_eq!(list.get(b), None);
+        assert_eq!(contents(&list), vec![1, 3]);
+
+        *list.get_mut(c).unwrap() = 30;
+        assert_eq!(list.pop_back(), Some(30));
+        assert_eq!(list.pop_front(), Some(1));
+        assert!(list.is_empty());
+        assert_eq!((list.front(), list.back()), (None, None));
+        assert_eq!(list.get(100), None);
+    }
+
+    #[test]
+    fn move_to_front_keeps_handles() {
+        let mut list: IndexList<_> = (0..4).collect();
+        let handles: Vec<_> = (0..4).collect();
+
+        list.move_to_front(handles[2]);
+        list.move_to_front(handles[3]);
+        list.move_to_front(handles[3]);
+        assert_eq!(contents(&list), vec![3, 2, 0, 1]);
+        assert_eq!(list.back(), Some(handles[1]));
+        assert_eq!(list.get(handles[2]), Some(&2));
+
+        list.remove(handles[1]);
+        list.move_to_front(handles[1]);
+        assert_eq!(contents(&list), vec![3, 2, 0]);
+        assert_eq!(format!("{:?}", list), "[3, 2, 0]");
+    }
+
+    #[test]
+    fn reuses_freed_slots() {
+        let mut list = IndexList::new();
+        for i in 0..100 {
+            list.push_back(i);
+        }
+        for i in 100..10_000 {
+            list.pop_front();
+            list.push_front(i);
+        }
+        assert_eq!(list.slots.len(), 100);
+        assert_eq!(contents(&list)[..2], [9_999, 1]);
+
+        list.clear();
+        assert!(contents(&list).is_empty());
+        assert_eq!(list.push_back(1), 0);
+    }
+}
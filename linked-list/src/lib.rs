@@ -1,45 +1,712 @@
+pub mod arena;
+pub mod bounded;
+pub mod concurrent;
+pub mod deque;
+pub mod index_list;
+pub mod lru;
+pub mod persistent;
+
+#[cfg(feature = "serde")]
+mod serde_impls;
+
+pub use arena::ArenaList;
+pub use deque::Deque;
+pub use index_list::IndexList;
+pub use lru::LruCache;
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+/// A singly linked list, used as a stack through `push` and `pop` at its
+/// head, which also keeps track of its last node so that elements can be
+/// added to the back.
+///
+/// Nodes are owned through raw pointers, as in [`Deque`], since a `Box`
+/// can't be pointed into from the tail while it owns the node: any use of
+/// the `Box` would invalidate the tail pointer. Each node is leaked from a
+/// `Box` when it is added, and turned back into one exactly once, when it
+/// is removed or the list is dropped.
 pub struct LinkedList<T> {
     head: Link<T>,
+    tail: Link<T>,
+    len: usize,
+    // The list owns its nodes, though it only holds pointers to them
+    marker: PhantomData<Box<Node<T>>>,
 }
 
 impl<T> LinkedList<T> {
     pub fn empty() -> Self {
-        Self { head: None }
+        Self {
+            head: None,
+            tail: None,
+            len: 0,
+            marker: PhantomData,
+        }
     }
 
     pub fn new(element: T) -> Self {
-        Self {
-            head: Some(Box::new(Node {
-                element,
-                next: None,
-            })),
-        }
+        let mut list = Self::empty();
+        list.push(element);
+        list
     }
 
     pub fn push(&mut self, element: T) {
-        // Equivalent to std::mem::replace(&mut self.head, None);
-        let old_head = self.head.take();
-        let new_head = Box::new(Node {
-            element,
-            next: old_head,
-        });
-        self.head = Some(new_head);
+        let node = new_node(element, self.head);
+        if self.head.is_none() {
+            self.tail = Some(node);
+        }
+        self.head = Some(node);
+        self.len += 1;
+    }
+
+    /// Add an element to the back of the list, so that it is popped last.
+    pub fn push_back(&mut self, element: T) {
+        let node = new_node(element, None);
+        match self.tail {
+            // SAFETY: the tail is a live node owned by the list
+            Some(tail) => unsafe { (*tail.as_ptr()).next = Some(node) },
+            None => self.head = Some(node),
+        }
+        self.tail = Some(node);
+        self.len += 1;
     }
 
     pub fn pop(&mut self) -> Option<T> {
         // Map can be used as a shorthand for matching and returning Some/None
-        self.head.take().map(|n| {
-            self.head = n.next;
-            n.element
+        self.head.map(|head| {
+            // SAFETY: the head was leaked from a Box when it was added, and
+            // is unlinked here so that nothing else can reach it
+            let node = unsafe { Box::from_raw(head.as_ptr()) };
+            self.head = node.next;
+            if self.head.is_none() {
+                self.tail = None;
+            }
+            self.len -= 1;
+            node.element
         })
     }
 
+    /// The number of elements, which is kept count of rather than found
+    /// by walking the list.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     pub fn peek(&self) -> Option<&T> {
-        match &self.head {
-            Some(n) => Some(&n.element),
-            None => None,
+        // SAFETY: the shared borrow of the list keeps the node alive and
+        // unchanged
+        self.head.map(|n| unsafe { &(*n.as_ptr()).element })
+    }
+
+    pub fn peek_mut(&mut self) -> Option<&mut T> {
+        // SAFETY: the mutable borrow of the list makes this the only
+        // reference to the element
+        self.head.map(|n| unsafe { &mut (*n.as_ptr()).element })
+    }
+
+    /// The last element, reached through the tail without walking the list.
+    pub fn peek_back(&self) -> Option<&T> {
+        // SAFETY: as in peek
+        self.tail.map(|n| unsafe { &(*n.as_ptr()).element })
+    }
+
+    pub fn peek_back_mut(&mut self) -> Option<&mut T> {
+        // SAFETY: as in peek_mut
+        self.tail.map(|n| unsafe { &mut (*n.as_ptr()).element })
+    }
+
+    /// The element at the given index, found by walking the list from the
+    /// head.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        // SAFETY: as in peek
+        self.node_at(index)
+            .map(|n| unsafe { &(*n.as_ptr()).element })
+    }
+
+    /// A mutable reference to the element at the given index, as with
+    /// [`LinkedList::get`].
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        // SAFETY: as in peek_mut
+        self.node_at(index)
+            .map(|n| unsafe { &mut (*n.as_ptr()).element })
+    }
+
+    fn node_at(&self, index: usize) -> Link<T> {
+        if index >= self.len {
+            return None;
+        }
+        let mut link = self.head;
+        for _ in 0..index {
+            // SAFETY: the nodes are live while the list is borrowed
+            link = link.and_then(|n| unsafe { (*n.as_ptr()).next });
+        }
+        link
+    }
+
+    pub fn contains(&self, element: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        self.iter().any(|e| e == element)
+    }
+
+    /// The index of the first element that matches the predicate.
+    pub fn position<P: FnMut(&T) -> bool>(&self, predicate: P) -> Option<usize> {
+        self.iter().position(predicate)
+    }
+
+    /// Keep only the elements that match the predicate, removing the rest
+    /// in a single pass.
+    pub fn retain<P: FnMut(&T) -> bool>(&mut self, mut predicate: P) {
+        self.extract_if(|e| !predicate(e)).for_each(drop);
+    }
+
+    /// An iterator that removes and yields the elements that match the
+    /// predicate, in order.
+    ///
+    /// Elements are removed lazily, as the iterator reaches them, so any it
+    /// doesn't get to before being dropped stay in the list.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use linked_list::LinkedList;
+    ///
+    /// let mut list: LinkedList<_> = (1..=6).collect();
+    /// let evens: Vec<_> = list.extract_if(|n| *n % 2 == 0).collect();
+    /// assert_eq!(evens, vec![2, 4, 6]);
+    /// assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![1, 3, 5]);
+    /// ```
+    pub fn extract_if<P: FnMut(&mut T) -> bool>(&mut self, predicate: P) -> ExtractIf<'_, T, P> {
+        ExtractIf {
+            cursor: self.cursor_front_mut(),
+            predicate,
+        }
+    }
+
+    /// A cursor starting at the head of the list, which can walk forwards
+    /// and edit the list as it goes.
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            prev: None,
+            list: self,
+        }
+    }
+
+    /// Iterate over references to the elements, from the head.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            next: self.head,
+            marker: PhantomData,
+        }
+    }
+
+    /// Iterate over mutable references to the elements, from the head.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            next: self.head,
+            marker: PhantomData,
+        }
+    }
+
+    /// Reverse the order of the elements in place, by relinking the nodes.
+    pub fn reverse(&mut self) {
+        let mut prev = None;
+        let mut link = self.head;
+        self.tail = self.head;
+        while let Some(n) = link {
+            // SAFETY: each node is live, and relinked only once
+            unsafe {
+                link = (*n.as_ptr()).next;
+                (*n.as_ptr()).next = prev;
+            }
+            prev = Some(n);
+        }
+        self.head = prev;
+    }
+
+    /// Move every element of `other` to the back of this list in O(1),
+    /// leaving `other` empty.
+    pub fn append(&mut self, other: &mut Self) {
+        let (first, last) = match (other.head.take(), other.tail.take()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return,
+        };
+        match self.tail {
+            // SAFETY: other's nodes now belong to this list alone
+            Some(tail) => unsafe { (*tail.as_ptr()).next = Some(first) },
+            None => self.head = Some(first),
+        }
+        self.tail = Some(last);
+        self.len += std::mem::replace(&mut other.len, 0);
+    }
+
+    /// Split the list in two at the given index, returning everything from
+    /// that index on. This walks the first `at` elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at` is greater than the length of the list.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        assert!(at <= self.len, "Cannot split off at a nonexistent index");
+        if at == 0 {
+            return std::mem::take(self);
+        }
+
+        // Find the last node that stays in this list
+        let mut last = self.head;
+        for _ in 1..at {
+            // SAFETY: the nodes are live while the list is borrowed
+            last = last.and_then(|n| unsafe { (*n.as_ptr()).next });
+        }
+        let last = last.unwrap();
+
+        // SAFETY: as above, and the nodes after it move to the new list
+        let first = unsafe { (*last.as_ptr()).next.take() };
+        let split = Self {
+            head: first,
+            tail: first.and(self.tail),
+            len: self.len - at,
+            marker: PhantomData,
+        };
+        self.tail = Some(last);
+        self.len = at;
+        split
+    }
+
+    /// Sort the list with a stable merge sort, in O(n log n) time. Nodes are
+    /// relinked rather than elements moved, and nothing is allocated.
+    pub fn sort(&mut self)
+    where
+        T: Ord,
+    {
+        self.sort_by(T::cmp);
+    }
+
+    /// Sort the list by the key each element maps to, as with
+    /// [`LinkedList::sort`].
+    pub fn sort_by_key<K: Ord, F: FnMut(&T) -> K>(&mut self, mut f: F) {
+        self.sort_by(|a, b| f(a).cmp(&f(b)));
+    }
+
+    /// Sort the list with a comparison function, as with
+    /// [`LinkedList::sort`].
+    pub fn sort_by<F: FnMut(&T, &T) -> Ordering>(&mut self, mut compare: F) {
+        // Merging runs bottom up, doubling their size on each pass, needs no
+        // recursion or scratch space. The list is emptied while the nodes
+        // are relinked, so a panicking comparison leaks them rather than
+        // leaving the list pointing at a half-sorted chain
+        let mut head = self.head.take();
+        let len = std::mem::replace(&mut self.len, 0);
+        self.tail = None;
+        if head.is_none() {
+            return;
+        }
+
+        // SAFETY: every node in the chain is live and owned by this function
+        // until the list is put back together
+        let next = |n: NonNull<Node<T>>| unsafe { (*n.as_ptr()).next };
+        let mut size = 1;
+        loop {
+            let mut left = head;
+            let mut tail: Link<T> = None;
+            let mut merges = 0;
+            head = None;
+
+            while left.is_some() {
+                merges += 1;
+
+                // The right run starts `size` nodes after the left one
+                let mut right = left;
+                let mut left_len = 0;
+                while left_len < size {
+                    left_len += 1;
+                    right = right.and_then(next);
+                    if right.is_none() {
+                        break;
+                    }
+                }
+                let mut right_len = size;
+
+                while left_len > 0 || (right_len > 0 && right.is_some()) {
+                    // Taking from the left run on ties keeps the sort stable
+                    let take_left = match (left, right) {
+                        (Some(l), Some(r)) if left_len > 0 && right_len > 0 => unsafe {
+                            compare(&(*l.as_ptr()).element, &(*r.as_ptr()).element)
+                                != Ordering::Greater
+                        },
+                        _ => left_len > 0,
+                    };
+                    let node = if take_left {
+                        let node = left.unwrap();
+                        left = next(node);
+                        left_len -= 1;
+                        node
+                    } else {
+                        let node = right.unwrap();
+                        right = next(node);
+                        right_len -= 1;
+                        node
+                    };
+
+                    match tail {
+                        Some(tail) => unsafe { (*tail.as_ptr()).next = Some(node) },
+                        None => head = Some(node),
+                    }
+                    tail = Some(node);
+                }
+
+                left = right;
+            }
+
+            if let Some(tail) = tail {
+                unsafe { (*tail.as_ptr()).next = None };
+            }
+            if merges <= 1 {
+                self.head = head;
+                self.tail = tail;
+                self.len = len;
+                return;
+            }
+            size *= 2;
+        }
+    }
+}
+
+impl<T> Drop for LinkedList<T> {
+    fn drop(&mut self) {
+        // Free the nodes one at a time, rather than recursing down the chain
+        // and overflowing the stack on long lists
+        while self.pop().is_some() {}
+    }
+}
+
+impl<T> Default for LinkedList<T> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for LinkedList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self).finish()
+    }
+}
+
+impl<T: Clone> Clone for LinkedList<T> {
+    fn clone(&self) -> Self {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T: PartialEq> PartialEq for LinkedList<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other)
+    }
+}
+
+impl<T: Eq> Eq for LinkedList<T> {}
+
+impl<T: Hash> Hash for LinkedList<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Like std's LinkedList, hash the length first so that lists of
+        // lists with the same elements split differently hash differently
+        state.write_usize(self.len);
+        for element in self {
+            element.hash(state);
+        }
+    }
+}
+
+impl<T: PartialOrd> PartialOrd for LinkedList<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other)
+    }
+}
+
+impl<T: Ord> Ord for LinkedList<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other)
+    }
+}
+
+// Collecting and extending add elements to the back of the list, so that
+// they come out of it in the order they went in
+impl<T> FromIterator<T> for LinkedList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = Self::empty();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<T> Extend<T> for LinkedList<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for element in iter {
+            self.push_back(element);
+        }
+    }
+}
+
+impl<'a, T: Copy + 'a> Extend<&'a T> for LinkedList<T> {
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+
+// SAFETY: the list owns its elements as a Vec<T> would, so it can be sent
+// or shared between threads whenever T can
+unsafe impl<T: Send> Send for LinkedList<T> {}
+unsafe impl<T: Sync> Sync for LinkedList<T> {}
+
+// A singly linked list can only be walked from the front, so none of its
+// iterators are double-ended
+
+/// An iterator that moves elements out of a list, from the head.
+pub struct IntoIter<T>(LinkedList<T>);
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.pop()
+    }
+}
+
+impl<T> IntoIterator for LinkedList<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self)
+    }
+}
+
+/// An iterator over references to the elements of a list.
+pub struct Iter<'a, T> {
+    next: Link<T>,
+    marker: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next.map(|n| {
+            // SAFETY: the list is borrowed for 'a, so its nodes live and stay
+            // unchanged that long
+            let n = unsafe { &*n.as_ptr() };
+            self.next = n.next;
+            &n.element
+        })
+    }
+}
+
+impl<'a, T> IntoIterator for &'a LinkedList<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over mutable references to the elements of a list.
+pub struct IterMut<'a, T> {
+    next: Link<T>,
+    marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next.map(|n| {
+            // SAFETY: the list is mutably borrowed for 'a, and each node is
+            // visited once, so each element is handed out only once
+            unsafe {
+                self.next = (*n.as_ptr()).next;
+                &mut (*n.as_ptr()).element
+            }
+        })
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut LinkedList<T> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// An iterator that removes the elements of a list that match a
+/// predicate, made by [`LinkedList::extract_if`].
+pub struct ExtractIf<'a, T, P> {
+    cursor: CursorMut<'a, T>,
+    predicate: P,
+}
+
+impl<'a, T, P: FnMut(&mut T) -> bool> Iterator for ExtractIf<'a, T, P> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(element) = self.cursor.current() {
+            if (self.predicate)(element) {
+                return self.cursor.remove_current();
+            }
+            self.cursor.move_next();
+        }
+        None
+    }
+}
+
+/// A cursor over a list, positioned either at an element or at the end of
+/// the list, after the last element.
+///
+/// Because nodes only link forwards, the cursor can't move backwards, and
+/// stays put once it reaches the end. Moving, peeking, editing around the
+/// current element and splicing in other lists are all O(1).
+pub struct CursorMut<'a, T> {
+    // The node before the current one, whose link holds the current node,
+    // or None when that is the list's head link
+    prev: Link<T>,
+    list: &'a mut LinkedList<T>,
+}
+
+impl<'a, T> CursorMut<'a, T> {
+    fn current_link(&self) -> Link<T> {
+        match self.prev {
+            // SAFETY: the cursor's nodes are live while it borrows the list
+            Some(prev) => unsafe { (*prev.as_ptr()).next },
+            None => self.list.head,
+        }
+    }
+
+    fn set_current_link(&mut self, link: Link<T>) {
+        match self.prev {
+            // SAFETY: as in current_link
+            Some(prev) => unsafe { (*prev.as_ptr()).next = link },
+            None => self.list.head = link,
+        }
+    }
+
+    pub fn current(&mut self) -> Option<&mut T> {
+        // SAFETY: the cursor borrows the list mutably, and this borrows the
+        // cursor mutably, so this is the only reference to the element
+        self.current_link()
+            .map(|n| unsafe { &mut (*n.as_ptr()).element })
+    }
+
+    pub fn peek_next(&mut self) -> Option<&mut T> {
+        // SAFETY: as in current
+        self.current_link()
+            .and_then(|n| unsafe { (*n.as_ptr()).next })
+            .map(|n| unsafe { &mut (*n.as_ptr()).element })
+    }
+
+    /// Move to the next element, or to the end of the list after the last
+    /// one.
+    pub fn move_next(&mut self) {
+        if let Some(current) = self.current_link() {
+            self.prev = Some(current);
+        }
+    }
+
+    /// Insert an element before the current one, leaving the cursor where
+    /// it is. At the end of the list this appends to it.
+    pub fn insert_before(&mut self, element: T) {
+        let current = self.current_link();
+        let node = new_node(element, current);
+        self.set_current_link(Some(node));
+        if current.is_none() {
+            self.list.tail = Some(node);
+        }
+        self.list.len += 1;
+        self.prev = Some(node);
+    }
+
+    /// Insert an element after the current one. At the end of the list
+    /// this appends to it, like [`CursorMut::insert_before`].
+    pub fn insert_after(&mut self, element: T) {
+        match self.current_link() {
+            // SAFETY: the current node is live while the list is borrowed
+            Some(current) => unsafe {
+                let node = new_node(element, (*current.as_ptr()).next);
+                (*current.as_ptr()).next = Some(node);
+                if self.list.tail == Some(current) {
+                    self.list.tail = Some(node);
+                }
+                self.list.len += 1;
+            },
+            None => self.insert_before(element),
         }
     }
+
+    /// Remove the current element, moving the cursor on to the next one.
+    pub fn remove_current(&mut self) -> Option<T> {
+        let current = self.current_link()?;
+        // SAFETY: the node is unlinked here, after which nothing else refers
+        // to it, and it was leaked from a Box when it was added
+        let node = unsafe { Box::from_raw(current.as_ptr()) };
+        self.set_current_link(node.next);
+        if node.next.is_none() {
+            self.list.tail = self.prev;
+        }
+        self.list.len -= 1;
+        Some(node.element)
+    }
+
+    /// Move every element of `list` in before the current one, leaving the
+    /// cursor where it is.
+    pub fn splice_before(&mut self, mut list: LinkedList<T>) {
+        let (first, last) = match (list.head.take(), list.tail.take()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return,
+        };
+        let current = self.current_link();
+        // SAFETY: list has been emptied, so its nodes now belong to this list
+        // alone
+        unsafe { (*last.as_ptr()).next = current };
+        self.set_current_link(Some(first));
+        if current.is_none() {
+            self.list.tail = Some(last);
+        }
+        self.list.len += std::mem::replace(&mut list.len, 0);
+        self.prev = Some(last);
+    }
+
+    /// Move every element of `list` in after the current one. At the end of
+    /// the list this appends them to it.
+    pub fn splice_after(&mut self, mut list: LinkedList<T>) {
+        let current = match self.current_link() {
+            Some(current) => current,
+            None => return self.splice_before(list),
+        };
+        let (first, last) = match (list.head.take(), list.tail.take()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return,
+        };
+        // SAFETY: as in splice_before, and the current node is live while
+        // the list is borrowed
+        unsafe {
+            (*last.as_ptr()).next = (*current.as_ptr()).next;
+            (*current.as_ptr()).next = Some(first);
+        }
+        if self.list.tail == Some(current) {
+            self.list.tail = Some(last);
+        }
+        self.list.len += std::mem::replace(&mut list.len, 0);
+    }
 }
 
 #[derive(Debug)]
@@ -48,47 +715,62 @@ struct Node<T> {
     next: Link<T>,
 }
 
-type Link<T> = Option<Box<Node<T>>>;
+type Link<T> = Option<NonNull<Node<T>>>;
+
+fn new_node<T>(element: T, next: Link<T>) -> NonNull<Node<T>> {
+    NonNull::from(Box::leak(Box::new(Node { element, next })))
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn node<T>(element: T, next: Link<T>) -> Link<T> {
+        Some(new_node(element, next))
+    }
+
+    /// A list made straight from a chain of nodes.
+    fn linked<T>(head: Link<T>) -> LinkedList<T> {
+        let mut tail = head;
+        let mut len = head.map_or(0, |_| 1);
+        while let Some(n) = tail.and_then(|n| unsafe { (*n.as_ptr()).next }) {
+            tail = Some(n);
+            len += 1;
+        }
+        LinkedList {
+            head,
+            tail,
+            len,
+            marker: PhantomData,
+        }
+    }
+
+    fn head_element<T: Copy>(list: &LinkedList<T>) -> T {
+        unsafe { (*list.head.unwrap().as_ptr()).element }
+    }
+
     #[test]
     fn it_works() {
-        let list = LinkedList {
-            head: Some(Box::new(Node {
-                element: 1023,
-                next: None,
-            })),
-        };
-        assert_eq!(list.head.unwrap().element, 1023);
+        let list = linked(node(1023, None));
+        assert_eq!(head_element(&list), 1023);
     }
 
     #[test]
     fn constructor_works() {
         let list = LinkedList::new(42);
-        assert_eq!(list.head.unwrap().element, 42);
+        assert_eq!(head_element(&list), 42);
     }
 
     #[test]
     fn can_push() {
         let mut list = LinkedList::empty();
         list.push(11);
-        assert_eq!(list.head.unwrap().element, 11);
+        assert_eq!(head_element(&list), 11);
     }
 
     #[test]
     fn can_pop() {
-        let mut list = LinkedList {
-            head: Some(Box::new(Node {
-                element: "one",
-                next: Some(Box::new(Node {
-                    element: "two",
-                    next: None,
-                })),
-            })),
-        };
+        let mut list = linked(node("one", node("two", None)));
         assert_eq!(list.pop(), Some("one"));
         assert_eq!(list.pop(), Some("two"));
     }
@@ -119,4 +801,360 @@ mod tests {
         assert_eq!(five, Some(5));
         assert_eq!(three, Some(3));
     }
+
+    #[test]
+    fn into_iter() {
+        let mut list = LinkedList::empty();
+        list.push(1);
+        list.push(2);
+        list.push(3);
+
+        let mut iter = list.into_iter();
+        assert_eq!(iter.next(), Some(3));
+        assert_eq!(iter.next(), Some(2));
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn iter() {
+        let mut list = LinkedList::empty();
+        list.push("a");
+        list.push("b");
+
+        let mut iter = list.iter();
+        assert_eq!(iter.next(), Some(&"b"));
+        assert_eq!(iter.next(), Some(&"a"));
+        assert_eq!(iter.next(), None);
+
+        let mut joined = String::new();
+        for s in &list {
+            joined.push_str(s);
+        }
+        assert_eq!(joined, "ba");
+        assert_eq!(list.peek(), Some(&"b"));
+    }
+
+    #[test]
+    fn iter_mut() {
+        let mut list = LinkedList::empty();
+        list.push(1);
+        list.push(2);
+        list.push(3);
+
+        for element in &mut list {
+            *element *= 10;
+        }
+        if let Some(last) = list.iter_mut().last() {
+            *last += 1;
+        }
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![30, 20, 11]);
+    }
+
+    #[test]
+    fn collect_and_extend_keep_order() {
+        let mut list: LinkedList<i32> = (1..=3).collect();
+        list.extend(vec![4, 5]);
+        list.extend(&[6]);
+        assert_eq!(
+            list.iter().copied().collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 5, 6]
+        );
+        assert_eq!(list.pop(), Some(1));
+
+        let mut empty = LinkedList::default();
+        empty.extend(Some("x"));
+        assert_eq!(empty.peek(), Some(&"x"));
+    }
+
+    #[test]
+    fn debug_and_clone() {
+        let list: LinkedList<_> = vec!["a", "b"].into_iter().collect();
+        let copy = list.clone();
+        assert_eq!(format!("{:?}", copy), r#"["a", "b"]"#);
+        assert_eq!(format!("{:?}", LinkedList::<u8>::empty()), "[]");
+        assert_eq!(list, copy);
+    }
+
+    #[test]
+    fn comparison_and_hashing() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::collections::HashSet;
+
+        let short: LinkedList<_> = vec![1, 2].into_iter().collect();
+        let long: LinkedList<_> = vec![1, 2, 3].into_iter().collect();
+        let other: LinkedList<_> = vec![1, 3].into_iter().collect();
+        assert!(short < long);
+        assert!(long < other);
+        assert_eq!(short.cmp(&short.clone()), Ordering::Equal);
+        assert_ne!(short, long);
+
+        let hash = |list: &LinkedList<LinkedList<i32>>| {
+            let mut hasher = DefaultHasher::new();
+            list.hash(&mut hasher);
+            hasher.finish()
+        };
+        // The same elements split into lists at different points must not
+        // hash alike, so the length of each list has to be hashed too
+        let lists = |split: Vec<Vec<i32>>| -> LinkedList<LinkedList<i32>> {
+            split
+                .into_iter()
+                .map(|list| list.into_iter().collect())
+                .collect()
+        };
+        let split_early = lists(vec![vec![1], vec![2, 3]]);
+        let split_late = lists(vec![vec![1, 2], vec![3]]);
+        assert_ne!(split_early, split_late);
+        assert_ne!(hash(&split_early), hash(&split_late));
+
+        let set: HashSet<_> = vec![short.clone(), long, short].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn cursor_walks_and_edits() {
+        let mut list: LinkedList<_> = vec![1, 2, 3].into_iter().collect();
+        let mut cursor = list.cursor_front_mut();
+        assert_eq!(cursor.current(), Some(&mut 1));
+        assert_eq!(cursor.peek_next(), Some(&mut 2));
+
+        cursor.insert_before(0);
+        cursor.insert_after(10);
+        assert_eq!(cursor.current(), Some(&mut 1));
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&mut 10));
+
+        cursor.move_next();
+        assert_eq!(cursor.remove_current(), Some(2));
+        assert_eq!(cursor.current(), Some(&mut 3));
+        if let Some(element) = cursor.current() {
+            *element = 30;
+        }
+
+        cursor.move_next();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.remove_current(), None);
+        cursor.insert_after(40);
+        cursor.insert_before(50);
+        cursor.move_next();
+        assert_eq!(cursor.current(), None);
+
+        assert_eq!(contents(&list), vec![0, 1, 10, 30, 40, 50]);
+    }
+
+    #[test]
+    fn cursor_splices() {
+        let mut list: LinkedList<_> = vec![1, 5].into_iter().collect();
+        let mut cursor = list.cursor_front_mut();
+        cursor.splice_after(vec![2, 3].into_iter().collect());
+        cursor.splice_before(vec![0].into_iter().collect());
+        cursor.splice_after(LinkedList::empty());
+        assert_eq!(cursor.current(), Some(&mut 1));
+
+        cursor.move_next();
+        cursor.move_next();
+        cursor.move_next();
+        cursor.splice_before(vec![4].into_iter().collect());
+        assert_eq!(cursor.current(), Some(&mut 5));
+        cursor.move_next();
+        cursor.splice_after(vec![6, 7].into_iter().collect());
+        assert_eq!(cursor.current(), None);
+
+        assert_eq!(contents(&list), vec![0, 1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    /// The elements of a list, checking on the way that its tail pointer
+    /// really is the last node, and its length is right.
+    fn contents<T: Copy>(list: &LinkedList<T>) -> Vec<T> {
+        let elements = list.iter().copied().collect::<Vec<_>>();
+        let mut last = list.head;
+        while let Some(n) = last.and_then(|n| unsafe { (*n.as_ptr()).next }) {
+            last = Some(n);
+        }
+        assert_eq!(last, list.tail);
+        assert_eq!(elements.len(), list.len());
+        elements
+    }
+
+    #[test]
+    fn len_is_tracked() {
+        let mut list = LinkedList::empty();
+        assert!(list.is_empty());
+        list.push(1);
+        list.push_back(2);
+        list.extend(vec![3, 4]);
+        assert_eq!(list.len(), 4);
+        list.pop();
+        assert_eq!(list.len(), 3);
+        assert!(!list.is_empty());
+        while list.pop().is_some() {}
+        assert_eq!(list.len(), 0);
+        assert_eq!(LinkedList::new('x').len(), 1);
+    }
+
+    #[test]
+    fn get_and_peek_mut() {
+        let mut list: LinkedList<_> = vec![1, 2, 3].into_iter().collect();
+        assert_eq!(list.get(0), Some(&1));
+        assert_eq!(list.get(2), Some(&3));
+        assert_eq!(list.get(3), None);
+
+        *list.peek_mut().unwrap() = 10;
+        *list.get_mut(2).unwrap() = 30;
+        assert_eq!(list.get_mut(3), None);
+        assert_eq!(contents(&list), vec![10, 2, 30]);
+        assert_eq!(LinkedList::<i32>::empty().peek_mut(), None);
+
+        *list.peek_back_mut().unwrap() += 3;
+        assert_eq!(list.peek_back(), Some(&33));
+        assert_eq!(LinkedList::<i32>::empty().peek_back(), None);
+    }
+
+    #[test]
+    fn contains_and_position() {
+        let list: LinkedList<_> = vec!['a', 'b', 'c', 'b'].into_iter().collect();
+        assert!(list.contains(&'c'));
+        assert!(!list.contains(&'z'));
+        assert_eq!(list.position(|c| *c == 'b'), Some(1));
+        assert_eq!(list.position(|c| *c == 'z'), None);
+    }
+
+    #[test]
+    fn retain() {
+        let mut list: LinkedList<_> = (0..10).collect();
+        list.retain(|n| n % 3 != 0);
+        assert_eq!(contents(&list), vec![1, 2, 4, 5, 7, 8]);
+
+        // Removing the last element moves the tail back
+        list.retain(|n| *n < 8);
+        list.push_back(100);
+        assert_eq!(contents(&list), vec![1, 2, 4, 5, 7, 100]);
+
+        list.retain(|_| false);
+        assert_eq!(contents(&list), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn extract_if_is_lazy() {
+        let mut list: LinkedList<_> = (0..10).collect();
+        {
+            let mut extracted = list.extract_if(|n| {
+                *n += 1;
+                *n % 2 == 0
+            });
+            assert_eq!(extracted.next(), Some(2));
+            assert_eq!(extracted.next(), Some(4));
+        }
+
+        // Elements the iterator didn't reach are left alone
+        assert_eq!(contents(&list), vec![1, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(
+            list.extract_if(|n| *n > 5).collect::<Vec<_>>(),
+            vec![6, 7, 8, 9]
+        );
+        assert_eq!(contents(&list), vec![1, 3, 4, 5]);
+    }
+
+    #[test]
+    fn retain_long_list() {
+        let n = if cfg!(miri) { 100 } else { 1_000_000 };
+        let mut list: LinkedList<_> = (0..n).collect();
+        list.retain(|i| i % 2 == 0);
+        assert_eq!(list.len(), n / 2);
+    }
+
+    #[test]
+    fn reverse() {
+        let mut list: LinkedList<_> = (1..=4).collect();
+        list.reverse();
+        assert_eq!(contents(&list), vec![4, 3, 2, 1]);
+        list.push_back(0);
+        assert_eq!(contents(&list), vec![4, 3, 2, 1, 0]);
+
+        let mut empty = LinkedList::<i32>::empty();
+        empty.reverse();
+        assert_eq!(contents(&empty), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn append() {
+        let mut list: LinkedList<_> = (1..=2).collect();
+        let mut other: LinkedList<_> = (3..=4).collect();
+        list.append(&mut other);
+        assert_eq!(contents(&list), vec![1, 2, 3, 4]);
+        assert_eq!(contents(&other), Vec::<i32>::new());
+
+        list.append(&mut other);
+        other.append(&mut list);
+        assert_eq!(contents(&list), Vec::<i32>::new());
+        assert_eq!(contents(&other), vec![1, 2, 3, 4]);
+        other.push_back(5);
+        assert_eq!(contents(&other), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn split_off() {
+        let mut list: LinkedList<_> = (0..5).collect();
+        let mut back = list.split_off(2);
+        assert_eq!(contents(&list), vec![0, 1]);
+        assert_eq!(contents(&back), vec![2, 3, 4]);
+
+        let none = back.split_off(3);
+        assert_eq!(contents(&back), vec![2, 3, 4]);
+        assert_eq!(contents(&none), Vec::<i32>::new());
+
+        let all = list.split_off(0);
+        assert_eq!(contents(&list), Vec::<i32>::new());
+        assert_eq!(contents(&all), vec![0, 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "nonexistent index")]
+    fn split_off_past_the_end() {
+        let mut list: LinkedList<_> = (0..2).collect();
+        list.split_off(3);
+    }
+
+    #[test]
+    fn sort_is_stable() {
+        let mut list: LinkedList<_> = vec![(3, 'a'), (1, 'b'), (3, 'c'), (2, 'd'), (1, 'e')]
+            .into_iter()
+            .collect();
+        list.sort_by_key(|&(key, _)| key);
+        assert_eq!(
+            contents(&list),
+            vec![(1, 'b'), (1, 'e'), (2, 'd'), (3, 'a'), (3, 'c')]
+        );
+    }
+
+    #[test]
+    fn sort_matches_vec() {
+        // A small linear congruential generator stands in for random input
+        let mut seed = 12345_u32;
+        for len in [0, 1, 2, 3, 7, 8, 100, 1_000] {
+            let mut expected = Vec::new();
+            for _ in 0..len {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                expected.push(seed % 100);
+            }
+            let mut list: LinkedList<_> = expected.iter().copied().collect();
+            list.sort();
+            expected.sort();
+            assert_eq!(contents(&list), expected);
+        }
+
+        let mut list: LinkedList<_> = (0..10).collect();
+        list.sort_by(|a, b| b.cmp(a));
+        assert_eq!(contents(&list), (0..10).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn drop_long_list() {
+        let n = if cfg!(miri) { 1_000 } else { 10_000_000 };
+        let mut list = LinkedList::empty();
+        for i in 0..n {
+            list.push(i);
+        }
+        drop(list);
+    }
 }
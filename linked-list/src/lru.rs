@@ -0,0 +1,502 @@
+//! A least-recently-used cache.
+//!
+//! Entries are kept in an [`IndexList`] in order of use, most recent at
+//! the front. Looking an entry up moves it to the front and evicting takes
+//! the entry at the back, both in O(1) given the entry's handle.
+//!
+//! Handles are found through a hash map from each key's hash to the first
+//! entry with that hash, and entries whose keys share a hash are chained
+//! through the entries themselves. That way each key is stored once, in
+//! its entry, and needn't be `Clone`.
+//!
+//! Every entry has a weight, 1 unless given another with
+//! [`LruCache::put_with_weight`], and the cache evicts entries once their
+//! total weight goes over its capacity. With the default weights the
+//! capacity is simply the number of entries.
+
+use crate::index_list::{self, IndexList};
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::mem;
+
+pub struct LruCache<K, V, S = RandomState> {
+    // From the most to the least recently used, the next to be evicted
+    entries: IndexList<Entry<K, V>>,
+    // The handle of the first entry whose key has each hash
+    handles: HashMap<u64, usize>,
+    hasher: S,
+    capacity: usize,
+    weight: usize,
+    on_evict: Option<Box<dyn FnMut(K, V) + Send>>,
+}
+
+struct Entry<K, V> {
+    key: K,
+    value: V,
+    weight: usize,
+    // The next entry whose key has the same hash
+    same_hash: Option<usize>,
+}
+
+impl<K: Hash + Eq, V> LruCache<K, V> {
+    /// An empty cache that holds entries up to a total weight of
+    /// `capacity`.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> LruCache<K, V, S> {
+    /// An empty cache that hashes its keys with `hasher`, as with
+    /// [`HashMap::with_hasher`].
+    pub fn with_hasher(capacity: usize, hasher: S) -> Self {
+        Self {
+            entries: IndexList::new(),
+            handles: HashMap::new(),
+            hasher,
+            capacity,
+            weight: 0,
+            on_evict: None,
+        }
+    }
+
+    /// Call `f` with every entry the cache evicts to stay within its
+    /// capacity. Entries removed with [`LruCache::pop`] or
+    /// [`LruCache::pop_lru`], or whose values are replaced by `put`, are
+    /// handed back to the caller instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use linked_list::LruCache;
+    /// use std::sync::mpsc;
+    ///
+    /// let (evicted, receiver) = mpsc::channel();
+    /// let mut cache = LruCache::new(2).on_evict(move |k, v| evicted.send((k, v)).unwrap());
+    /// cache.put("a", 1);
+    /// cache.put("b", 2);
+    /// cache.get(&"a");
+    /// cache.put("c", 3);
+    /// assert_eq!(receiver.try_recv(), Ok(("b", 2)));
+    /// ```
+    pub fn on_evict<F: FnMut(K, V) + Send + 'static>(mut self, f: F) -> Self {
+        self.on_evict = Some(Box::new(f));
+        self
+    }
+
+    /// The greatest total weight of the entries the cache holds.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The total weight of the entries in the cache.
+    pub fn weight(&self) -> usize {
+        self.weight
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    /// Insert an entry with a weight of 1, as with
+    /// [`LruCache::put_with_weight`].
+    pub fn put(&mut self, key: K, value: V) -> Option<V> {
+        self.put_with_weight(key, value, 1)
+    }
+
+    /// Insert an entry as the most recently used, returning the value it
+    /// replaced if the key was already in the cache. The least recently
+    /// used entries are then evicted until the cache is back within its
+    /// capacity.
+    ///
+    /// An entry that weighs more than the whole capacity can never fit, so
+    /// it is evicted straight away, leaving the other entries alone, though
+    /// it still replaces any value the key had.
+    pub fn put_with_weight(&mut self, key: K, value: V, weight: usize) -> Option<V> {
+        if weight > self.capacity {
+            let old = self.pop(&key);
+            self.evicted(key, value);
+            return old;
+        }
+
+        let old = match self.find(&key) {
+            Some(handle) => {
+                self.entries.move_to_front(handle);
+                let entry = self.entry_mut(handle);
+                let old_weight = mem::replace(&mut entry.weight, weight);
+                let old = mem::replace(&mut entry.value, value);
+                self.weight = self.weight - old_weight + weight;
+                Some(old)
+            }
+            None => {
+                let hash = self.hasher.hash_one(&key);
+                let handle = self.entries.push_front(Entry {
+                    key,
+                    value,
+                    weight,
+                    same_hash: self.handles.get(&hash).copied(),
+                });
+                self.handles.insert(hash, handle);
+                self.weight += weight;
+                None
+            }
+        };
+        self.evict();
+        old
+    }
+
+    /// Look up a value, marking its entry as the most recently used.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_mut(key).map(|v| &*v)
+    }
+
+    /// Look up a value mutably, marking its entry as the most recently
+    /// used.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let handle = self.find(key)?;
+        self.entries.move_to_front(handle);
+        Some(&mut self.entry_mut(handle).value)
+    }
+
+    /// Look up a value without changing how recently it was used.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let handle = self.find(key)?;
+        Some(&self.entry(handle).value)
+    }
+
+    /// The least recently used entry, which is the next to be evicted.
+    pub fn peek_lru(&self) -> Option<(&K, &V)> {
+        self.entries.back().map(|handle| {
+            let entry = self.entry(handle);
+            (&entry.key, &entry.value)
+        })
+    }
+
+    /// Remove an entry, returning its value.
+    pub fn pop<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let handle = self.find(key)?;
+        Some(self.remove(handle).1)
+    }
+
+    /// Remove the least recently used entry.
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        let handle = self.entries.back()?;
+        Some(self.remove(handle))
+    }
+
+    /// Change the capacity, evicting the least recently used entries if the
+    /// cache no longer fits in it.
+    pub fn resize(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    /// Remove every entry, without calling the eviction callback.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.handles.clear();
+        self.weight = 0;
+    }
+
+    /// Iterate over the entries from the most to the least recently used,
+    /// without changing their order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            entries: self.entries.iter(),
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.weight > self.capacity {
+            let (key, value) = match self.pop_lru() {
+                Some(entry) => entry,
+                None => break,
+            };
+            self.evicted(key, value);
+        }
+    }
+
+    fn evicted(&mut self, key: K, value: V) {
+        if let Some(on_evict) = &mut self.on_evict {
+            on_evict(key, value);
+        }
+    }
+
+    // The handle of the entry with a key, following the chain of entries
+    // with its hash. `Borrow` guarantees the key hashes as `K` would.
+    fn find<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut link = self.handles.get(&self.hasher.hash_one(key)).copied();
+        while let Some(handle) = link {
+            let entry = self.entry(handle);
+            if entry.key.borrow() == key {
+                return Some(handle);
+            }
+            link = entry.same_hash;
+        }
+        None
+    }
+
+    // Take an entry out of its hash's chain and then the list
+    fn remove(&mut self, handle: usize) -> (K, V) {
+        let (hash, same_hash) = {
+            let entry = self.entry(handle);
+            (self.hasher.hash_one(&entry.key), entry.same_hash)
+        };
+        let first = self.handles[&hash];
+        if first == handle {
+            match same_hash {
+                Some(next) => self.handles.insert(hash, next),
+                None => self.handles.remove(&hash),
+            };
+        } else {
+            let mut prev = first;
+            while self.entry(prev).same_hash != Some(handle) {
+                prev = self.entry(prev).same_hash.expect("entry is in its chain");
+            }
+            self.entry_mut(prev).same_hash = same_hash;
+        }
+
+        let entry = self
+            .entries
+            .remove(handle)
+            .expect("cache holds a stale handle");
+        self.weight -= entry.weight;
+        (entry.key, entry.value)
+    }
+
+    fn entry(&self, handle: usize) -> &Entry<K, V> {
+        self.entries
+            .get(handle)
+            .expect("cache holds a stale handle")
+    }
+
+    fn entry_mut(&mut self, handle: usize) -> &mut Entry<K, V> {
+        self.entries
+            .get_mut(handle)
+            .expect("cache holds a stale handle")
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, S> fmt::Debug for LruCache<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries = self.entries.iter().map(|entry| (&entry.key, &entry.value));
+        f.debug_map().entries(entries).finish()
+    }
+}
+
+/// An iterator over the entries of a cache, from the most to the least
+/// recently used.
+pub struct Iter<'a, K, V> {
+    entries: index_list::Iter<'a, Entry<K, V>>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(|entry| (&entry.key, &entry.value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn keys<V, S: BuildHasher>(cache: &LruCache<&'static str, V, S>) -> Vec<&'static str> {
+        cache.iter().map(|(k, _)| *k).collect()
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = LruCache::new(3);
+        assert_eq!(cache.put("a", 1), None);
+        cache.put("b", 2);
+        cache.put("c", 3);
+        assert_eq!(keys(&cache), vec!["c", "b", "a"]);
+
+        // Using an entry saves it from eviction, peeking doesn't
+        assert_eq!(cache.get("a"), Some(&1));
+        assert_eq!(cache.peek("b"), Some(&2));
+        assert_eq!(cache.peek_lru(), Some((&"b", &2)));
+        cache.put("d", 4);
+        assert_eq!(keys(&cache), vec!["d", "a", "c"]);
+        assert!(!cache.contains("b"));
+
+        // Replacing a value returns the old one and counts as a use
+        assert_eq!(cache.put("c", 30), Some(3));
+        *cache.get_mut("d").unwrap() += 1;
+        assert_eq!(keys(&cache), vec!["d", "c", "a"]);
+        assert_eq!(cache.peek("d"), Some(&5));
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn pop_and_resize() {
+        let mut cache = LruCache::new(4);
+        for (i, key) in ["a", "b", "c", "d"].iter().enumerate() {
+            cache.put(*key, i);
+        }
+        assert_eq!(cache.pop("b"), Some(1));
+        assert_eq!(cache.pop("b"), None);
+        assert_eq!(cache.pop_lru(), Some(("a", 0)));
+        assert_eq!(keys(&cache), vec!["d", "c"]);
+
+        cache.put("e", 4);
+        cache.resize(1);
+        assert_eq!(keys(&cache), vec!["e"]);
+        assert_eq!(cache.capacity(), 1);
+
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.pop_lru(), None);
+        cache.put("f", 5);
+        assert_eq!(keys(&cache), vec!["f"]);
+    }
+
+    #[test]
+    fn weights() {
+        let mut cache = LruCache::new(10);
+        cache.put_with_weight("small", (), 2);
+        cache.put_with_weight("medium", (), 5);
+        cache.put_with_weight("large", (), 3);
+        assert_eq!(cache.weight(), 10);
+
+        // Making room for 6 means evicting both of the older entries
+        cache.put_with_weight("new", (), 6);
+        assert_eq!(keys(&cache), vec!["new", "large"]);
+        assert_eq!(cache.weight(), 9);
+
+        // Reweighing an entry can evict others
+        cache.put_with_weight("large", (), 4);
+        assert_eq!(keys(&cache), vec!["large", "new"]);
+        cache.put_with_weight("large", (), 5);
+        assert_eq!(keys(&cache), vec!["large"]);
+        assert_eq!(cache.weight(), 5);
+
+        // An entry too heavy to ever fit is evicted at once
+        cache.put_with_weight("huge", (), 11);
+        assert!(!cache.contains("huge"));
+        assert_eq!(keys(&cache), vec!["large"]);
+    }
+
+    #[test]
+    fn eviction_callback() {
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&evicted);
+        let mut cache = LruCache::new(2).on_evict(move |k, v| sink.lock().unwrap().push((k, v)));
+
+        cache.put("a", 1);
+        cache.put("b", 2);
+        cache.put("c", 3);
+        // Replaced, popped and cleared entries don't count as evicted
+        cache.put("c", 30);
+        cache.pop("b");
+        cache.put("d", 4);
+        cache.put("e", 5);
+        cache.resize(0);
+        cache.clear();
+
+        assert_eq!(
+            *evicted.lock().unwrap(),
+            vec![("a", 1), ("c", 30), ("d", 4), ("e", 5)]
+        );
+    }
+
+    #[test]
+    fn evicted_entries_leave_no_handles() {
+        let mut cache = LruCache::new(100);
+        for i in 0..10_000 {
+            cache.put(i, i * 2);
+        }
+        assert_eq!(cache.len(), 100);
+        assert_eq!(cache.handles.len(), 100);
+        assert_eq!(cache.peek(&9_999), Some(&19_998));
+        assert_eq!(cache.peek(&9_899), None);
+        assert_eq!(format!("{:?}", LruCache::<u8, u8>::new(1)), "{}");
+    }
+
+    // Hashes every key alike, so that every entry shares one chain
+    #[derive(Default)]
+    struct Collide;
+
+    impl BuildHasher for Collide {
+        type Hasher = Constant;
+
+        fn build_hasher(&self) -> Constant {
+            Constant
+        }
+    }
+
+    struct Constant;
+
+    impl std::hash::Hasher for Constant {
+        fn finish(&self) -> u64 {
+            0
+        }
+
+        fn write(&mut self, _: &[u8]) {}
+    }
+
+    #[test]
+    fn colliding_keys() {
+        let mut cache = LruCache::with_hasher(3, Collide);
+        cache.put("a", 1);
+        cache.put("b", 2);
+        cache.put("c", 3);
+        assert_eq!(cache.handles.len(), 1);
+
+        // Remove from the middle, the end and the start of the chain
+        assert_eq!(cache.pop("b"), Some(2));
+        assert_eq!(cache.pop_lru(), Some(("a", 1)));
+        cache.put("d", 4);
+        assert_eq!(cache.pop("d"), Some(4));
+        assert_eq!((cache.peek("c"), cache.peek("a")), (Some(&3), None));
+
+        cache.put("e", 5);
+        cache.put("f", 6);
+        assert_eq!(cache.put("c", 30), Some(3));
+        cache.put("g", 7);
+        assert_eq!(keys(&cache), vec!["g", "c", "f"]);
+        for key in &["g", "c", "f"] {
+            assert!(cache.contains(key));
+        }
+        cache.resize(0);
+        assert!(cache.handles.is_empty());
+    }
+}
@@ -0,0 +1,176 @@
+//! A persistent, immutable list, where every version stays usable.
+//!
+//! Prepending to a list doesn't change it, but returns a new list whose
+//! tail is the old one, shared through an `Rc` rather than copied. This
+//! makes keeping every past version, as an undo history does, as cheap as
+//! keeping the newest one. Lists can't be sent between threads; a version
+//! for that would swap `Rc` for `Arc`.
+
+use std::fmt;
+use std::rc::Rc;
+
+pub struct List<T> {
+    head: Link<T>,
+}
+
+type Link<T> = Option<Rc<Node<T>>>;
+
+struct Node<T> {
+    element: T,
+    next: Link<T>,
+}
+
+impl<T> List<T> {
+    pub fn new() -> Self {
+        Self { head: None }
+    }
+
+    /// A new list with `element` at its head, followed by this list.
+    pub fn prepend(&self, element: T) -> List<T> {
+        List {
+            head: Some(Rc::new(Node {
+                element,
+                next: self.head.clone(),
+            })),
+        }
+    }
+
+    /// This list without its head, or the empty list if it is already
+    /// empty.
+    pub fn tail(&self) -> List<T> {
+        List {
+            head: self.head.as_ref().and_then(|n| n.next.clone()),
+        }
+    }
+
+    pub fn head(&self) -> Option<&T> {
+        self.head.as_ref().map(|n| &n.element)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    /// Iterate over references to the elements, from the head.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            next: self.head.as_deref(),
+        }
+    }
+}
+
+impl<T> Drop for List<T> {
+    fn drop(&mut self) {
+        // Free nodes one at a time for as long as this list is their only
+        // owner. The first node that is still shared stays, along with the
+        // rest of the list behind it, for its other owners
+        let mut link = self.head.take();
+        while let Some(node) = link {
+            match Rc::try_unwrap(node) {
+                Ok(mut node) => link = node.next.take(),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+// Cloning copies the pointer to the head, not the elements, so it doesn't
+// need T: Clone
+impl<T> Clone for List<T> {
+    fn clone(&self) -> Self {
+        Self {
+            head: self.head.clone(),
+        }
+    }
+}
+
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for List<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self).finish()
+    }
+}
+
+/// An iterator over references to the elements of a list.
+pub struct Iter<'a, T> {
+    next: Option<&'a Node<T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next.map(|n| {
+            self.next = n.next.as_deref();
+            &n.element
+        })
+    }
+}
+
+impl<'a, T> IntoIterator for &'a List<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prepend_head_and_tail() {
+        let empty = List::new();
+        assert_eq!(empty.head(), None);
+        assert!(empty.tail().is_empty());
+
+        let one = empty.prepend(1);
+        let two = one.prepend(2);
+        let three = two.prepend(3);
+        assert_eq!(three.head(), Some(&3));
+        assert_eq!(three.tail().head(), Some(&2));
+        assert_eq!(three.tail().tail().tail().head(), None);
+
+        // Older versions are unaffected
+        assert_eq!(one.iter().collect::<Vec<_>>(), vec![&1]);
+        assert_eq!(format!("{:?}", three), "[3, 2, 1]");
+    }
+
+    #[test]
+    fn versions_share_tails() {
+        let base = List::new().prepend("base");
+        let left = base.prepend("left");
+        let right = base.prepend("right");
+
+        let node = |list: &List<&'static str>| Rc::as_ptr(list.head.as_ref().unwrap());
+        assert_eq!(node(&left.tail()), node(&base));
+        assert_eq!(node(&right.tail()), node(&base));
+        assert_eq!(Rc::strong_count(base.head.as_ref().unwrap()), 3);
+    }
+
+    #[test]
+    fn drop_stops_at_shared_nodes() {
+        let shared = (0..100).fold(List::new(), |list, i| list.prepend(i));
+        let longer = (100..200).fold(shared.clone(), |list, i| list.prepend(i));
+        drop(longer);
+        assert_eq!(shared.iter().count(), 100);
+        assert_eq!(shared.head(), Some(&99));
+        assert_eq!(Rc::strong_count(shared.head.as_ref().unwrap()), 1);
+    }
+
+    #[test]
+    fn drop_long_list() {
+        let mut list = List::new();
+        for i in 0..1_000_000 {
+            list = list.prepend(i);
+        }
+        drop(list);
+    }
+}
@@ -0,0 +1,78 @@
+//! Serde support for [`LinkedList`] and [`ArenaList`], behind the `serde`
+//! feature.
+//!
+//! A list is written as a plain sequence, from head to tail, so it reads
+//! back in the same order and is interchangeable with a `Vec` on disk.
+//!
+//! The tests are in `tests/serde.rs`, which Cargo only builds with the
+//! feature on: run them with `cargo test --features serde`.
+
+use crate::{ArenaList, LinkedList};
+use serde::de::{Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeSeq, Serializer};
+use std::fmt;
+use std::iter;
+use std::marker::PhantomData;
+
+impl<T: Serialize> Serialize for LinkedList<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_seq(serializer, self.len(), self)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for LinkedList<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(ListVisitor(PhantomData))
+    }
+}
+
+// An arena list is written the same way, so the two can be read back as
+// each other, and its vacant slots are never written out
+impl<T: Serialize> Serialize for ArenaList<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_seq(serializer, self.len(), self)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for ArenaList<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(ListVisitor(PhantomData))
+    }
+}
+
+fn serialize_seq<'a, S, T, I>(serializer: S, len: usize, elements: I) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: Serialize + 'a,
+    I: IntoIterator<Item = &'a T>,
+{
+    let mut seq = serializer.serialize_seq(Some(len))?;
+    for element in elements {
+        seq.serialize_element(element)?;
+    }
+    seq.end()
+}
+
+/// Reads a sequence into either kind of list, pushing each element to the
+/// back so that the order is kept.
+struct ListVisitor<L, T>(PhantomData<(L, T)>);
+
+impl<'de, L, T> Visitor<'de> for ListVisitor<L, T>
+where
+    L: Default + Extend<T>,
+    T: Deserialize<'de>,
+{
+    type Value = L;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a sequence")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut list = L::default();
+        while let Some(element) = seq.next_element()? {
+            list.extend(iter::once(element));
+        }
+        Ok(list)
+    }
+}
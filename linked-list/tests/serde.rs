@@ -0,0 +1,57 @@
+//! Round trips through serde formats, for the `serde` feature. This only
+//! builds with the feature on: run it with `cargo test --features serde`.
+
+use linked_list::{ArenaList, LinkedList};
+
+fn list() -> LinkedList<String> {
+    ["first", "second", "third"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+#[test]
+fn json_round_trip() {
+    let json = serde_json::to_string(&list()).unwrap();
+    assert_eq!(json, r#"["first","second","third"]"#);
+
+    let back: LinkedList<String> = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, list());
+    assert_eq!(back.len(), 3);
+
+    // Lists read the same as the Vecs they used to be stored as
+    let vec: Vec<String> = serde_json::from_str(&json).unwrap();
+    assert_eq!(serde_json::to_string(&vec).unwrap(), json);
+    let empty: LinkedList<u8> = serde_json::from_str("[]").unwrap();
+    assert!(empty.is_empty());
+    assert!(serde_json::from_str::<LinkedList<u8>>("{}").is_err());
+}
+
+#[test]
+fn bincode_round_trip() {
+    let bytes = bincode::serialize(&list()).unwrap();
+    let back: LinkedList<String> = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(back, list());
+
+    // Pushing to the back after reading in must still work
+    let mut back = back;
+    back.push_back("fourth".to_string());
+    assert_eq!(back.iter().last().unwrap(), "fourth");
+}
+
+#[test]
+fn arena_list_round_trip() {
+    // Vacant slots left by popping aren't written out
+    let mut arena: ArenaList<String> = list().into_iter().collect();
+    arena.push("zeroth".to_string());
+    arena.pop();
+    let json = serde_json::to_string(&arena).unwrap();
+    assert_eq!(json, serde_json::to_string(&list()).unwrap());
+
+    // Either kind of list reads back what the other wrote
+    let linked: LinkedList<String> = serde_json::from_str(&json).unwrap();
+    assert_eq!(linked, list());
+    let back: ArenaList<String> =
+        bincode::deserialize(&bincode::serialize(&arena).unwrap()).unwrap();
+    assert_eq!(back, arena);
+}
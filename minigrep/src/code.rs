@@ -0,0 +1,409 @@
+//! # code
+//!
+//! A rough lexer that finds the comments and string literals in source
+//! code, so that searches can skip matches inside them, or look only
+//! there. It knows just enough of each language to tell where those
+//! regions start and end: Rust, the C family (C, C++, Java, JavaScript,
+//! Go and friends), Python and shell scripts. Anything it doesn't
+//! recognize is treated as code.
+
+use std::path::Path;
+use std::str::FromStr;
+
+/// Languages with comment and string syntax the lexer understands.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Language {
+    Rust,
+    /// C and languages that borrow its comments and quotes
+    C,
+    Python,
+    Shell,
+}
+
+impl Language {
+    /// Guess the language of a file from its extension.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minigrep::code::Language;
+    /// use std::path::Path;
+    ///
+    /// assert_eq!(Some(Language::Rust), Language::from_path(Path::new("src/lib.rs")));
+    /// assert_eq!(None, Language::from_path(Path::new("poem.txt")));
+    /// ```
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "rs" => Some(Language::Rust),
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "java" | "js" | "jsx" | "ts"
+            | "tsx" | "go" | "cs" | "kt" | "swift" | "scala" => Some(Language::C),
+            "py" | "pyi" => Some(Language::Python),
+            "sh" | "bash" | "zsh" => Some(Language::Shell),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rust" => Ok(Language::Rust),
+            "c" => Ok(Language::C),
+            "python" => Ok(Language::Python),
+            "shell" => Ok(Language::Shell),
+            _ => Err("Language must be one of rust, c, python or shell"),
+        }
+    }
+}
+
+/// The kind of source text a match falls in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Region {
+    Code,
+    Comment,
+    /// String and character literals
+    String,
+}
+
+impl FromStr for Region {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "code" => Ok(Region::Code),
+            "comment" | "comments" => Ok(Region::Comment),
+            "string" | "strings" => Ok(Region::String),
+            _ => Err("Region must be one of code, comments or strings"),
+        }
+    }
+}
+
+/// A comment or string literal, as a range of byte offsets.
+#[derive(Debug, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub region: Region,
+}
+
+/// Every comment and string literal in `contents`, in order. Delimiters
+/// are included in the spans, and an unterminated comment or string runs
+/// to the end of its line, or of the file where the language allows it
+/// to span lines.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::code::{spans, Language, Region, Span};
+///
+/// let spans = spans("let s = \"hi\"; // greet", Language::Rust);
+/// assert_eq!(
+///     vec![
+///         Span { start: 8, end: 12, region: Region::String },
+///         Span { start: 14, end: 22, region: Region::Comment },
+///     ],
+///     spans
+/// );
+/// ```
+pub fn spans(contents: &str, language: Language) -> Vec<Span> {
+    let b = contents.as_bytes();
+    let mut spans = Vec::new();
+    let mut i = 0;
+
+    while i < b.len() {
+        let found = match language {
+            Language::Rust => rust(contents, i),
+            Language::C => c(b, i),
+            Language::Python => python(b, i),
+            Language::Shell => shell(b, i),
+        };
+
+        match found {
+            Some((region, end)) => {
+                spans.push(Span {
+                    start: i,
+                    end,
+                    region,
+                });
+                i = end;
+            }
+            // Skip escaped characters outside of quotes in shell scripts
+            None if language == Language::Shell && b[i] == b'\\' => i += 2,
+            None => i += 1,
+        }
+    }
+
+    spans
+}
+
+/// The region that the byte at `offset` falls in, given the spans found
+/// for the file.
+pub fn region_at(spans: &[Span], offset: usize) -> Region {
+    let i = spans.partition_point(|span| span.end <= offset);
+    match spans.get(i) {
+        Some(span) if span.start <= offset => span.region,
+        _ => Region::Code,
+    }
+}
+
+fn rust(contents: &str, i: usize) -> Option<(Region, usize)> {
+    let b = contents.as_bytes();
+    let rest = &b[i..];
+
+    if rest.starts_with(b"//") {
+        return Some((Region::Comment, line_end(b, i)));
+    }
+    if rest.starts_with(b"/*") {
+        return Some((Region::Comment, block_end(b, i, true)));
+    }
+
+    let after_ident = i > 0 && is_ident(b[i - 1]);
+    if !after_ident {
+        let raw = if rest.starts_with(b"br") { 2 } else { 1 };
+        if rest.starts_with(b"r") || rest.starts_with(b"br") {
+            let hashes = rest[raw..].iter().take_while(|&&c| c == b'#').count();
+            if rest.get(raw + hashes) == Some(&b'"') {
+                return Some((Region::String, raw_end(b, i + raw + hashes + 1, hashes)));
+            }
+        }
+    }
+
+    match b[i] {
+        b'"' => Some((Region::String, quoted_end(b, i, b'"', true))),
+        // A quote is a character literal if it closes right after one
+        // character or an escape, and a lifetime otherwise
+        b'\'' => match contents[i + 1..].chars().next() {
+            Some('\\') => Some((Region::String, quoted_end(b, i, b'\'', false))),
+            Some(c) if b.get(i + 1 + c.len_utf8()) == Some(&b'\'') => {
+                Some((Region::String, i + 2 + c.len_utf8()))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn c(b: &[u8], i: usize) -> Option<(Region, usize)> {
+    let rest = &b[i..];
+    if rest.starts_with(b"//") {
+        Some((Region::Comment, line_end(b, i)))
+    } else if rest.starts_with(b"/*") {
+        Some((Region::Comment, block_end(b, i, false)))
+    } else if b[i] == b'"' || b[i] == b'\'' {
+        Some((Region::String, quoted_end(b, i, b[i], false)))
+    } else {
+        None
+    }
+}
+
+fn python(b: &[u8], i: usize) -> Option<(Region, usize)> {
+    let rest = &b[i..];
+    if b[i] == b'#' {
+        Some((Region::Comment, line_end(b, i)))
+    } else if rest.starts_with(b"\"\"\"") || rest.starts_with(b"'''") {
+        let close = &rest[..3];
+        let mut j = i + 3;
+        while j < b.len() && !b[j..].starts_with(close) {
+            j += if b[j] == b'\\' { 2 } else { 1 };
+        }
+        Some((Region::String, (j + 3).min(b.len())))
+    } else if b[i] == b'"' || b[i] == b'\'' {
+        Some((Region::String, quoted_end(b, i, b[i], false)))
+    } else {
+        None
+    }
+}
+
+fn shell(b: &[u8], i: usize) -> Option<(Region, usize)> {
+    match b[i] {
+        // `#` only starts a comment at the beginning of a word
+        b'#' if i == 0 || b[i - 1].is_ascii_whitespace() || b";|&()".contains(&b[i - 1]) => {
+            Some((Region::Comment, line_end(b, i)))
+        }
+        b'\'' => {
+            let end = b[i + 1..]
+                .iter()
+                .position(|&c| c == b'\'')
+                .map_or(b.len(), |j| i + j + 2);
+            Some((Region::String, end))
+        }
+        b'"' => Some((Region::String, quoted_end(b, i, b'"', true))),
+        _ => None,
+    }
+}
+
+fn is_ident(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+/// The end of the line that `i` is on, without the newline.
+fn line_end(b: &[u8], i: usize) -> usize {
+    b[i..]
+        .iter()
+        .position(|&c| c == b'\n')
+        .map_or(b.len(), |j| i + j)
+}
+
+/// The end of the block comment opening at `i`, after its `*/`.
+fn block_end(b: &[u8], i: usize, nested: bool) -> usize {
+    let mut depth = 0;
+    let mut j = i;
+    while j < b.len() {
+        if b[j..].starts_with(b"/*") && (nested || depth == 0) {
+            depth += 1;
+            j += 2;
+        } else if b[j..].starts_with(b"*/") {
+            depth -= 1;
+            j += 2;
+            if depth == 0 {
+                return j;
+            }
+        } else {
+            j += 1;
+        }
+    }
+    b.len()
+}
+
+/// The end of the string opening with `quote` at `i`, after the closing
+/// quote. Backslashes escape the next character.
+fn quoted_end(b: &[u8], i: usize, quote: u8, multiline: bool) -> usize {
+    let mut j = i + 1;
+    while j < b.len() {
+        match b[j] {
+            b'\\' => j += 2,
+            c if c == quote => return j + 1,
+            b'\n' if !multiline => return j,
+            _ => j += 1,
+        }
+    }
+    b.len()
+}
+
+/// The end of a raw string whose contents start at `i`, after the closing
+/// quote and `hashes` hash signs.
+fn raw_end(b: &[u8], i: usize, hashes: usize) -> usize {
+    let mut j = i;
+    while j < b.len() {
+        if b[j] == b'"'
+            && b[j + 1..]
+                .iter()
+                .take(hashes)
+                .filter(|&&c| c == b'#')
+                .count()
+                == hashes
+        {
+            return j + 1 + hashes;
+        }
+        j += 1;
+    }
+    b.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The text of each span, paired with its region.
+    fn regions(contents: &str, language: Language) -> Vec<(Region, &str)> {
+        spans(contents, language)
+            .iter()
+            .map(|span| (span.region, &contents[span.start..span.end]))
+            .collect()
+    }
+
+    #[test]
+    fn rust_comments_and_strings() {
+        let source = r###"/* outer /* inner */ still */
+fn f<'a>(x: &'a str) -> char { // done
+    let r = r#"raw "quoted""#;
+    let s = "esc \" aped";
+    'x'
+}"###;
+        assert_eq!(
+            vec![
+                (Region::Comment, "/* outer /* inner */ still */"),
+                (Region::Comment, "// done"),
+                (Region::String, r###"r#"raw "quoted""#"###),
+                (Region::String, r#""esc \" aped""#),
+                (Region::String, "'x'"),
+            ],
+            regions(source, Language::Rust)
+        );
+    }
+
+    #[test]
+    fn rust_char_literals() {
+        assert_eq!(
+            vec![(Region::String, "'é'"), (Region::String, r"'\n'")],
+            regions("['é', '\\n', 'static]", Language::Rust)
+        );
+        // An identifier ending in `r` doesn't start a raw string
+        assert_eq!(
+            vec![(Region::String, "\"x\"")],
+            regions("bar\"x\"", Language::Rust)
+        );
+    }
+
+    #[test]
+    fn c_family_comments_and_strings() {
+        assert_eq!(
+            vec![
+                (Region::Comment, "/* a /* b */"),
+                (Region::String, "\"//\""),
+                (Region::String, "'\\''"),
+                (Region::Comment, "// end"),
+            ],
+            regions("/* a /* b */ x = \"//\" + '\\''; // end", Language::C)
+        );
+    }
+
+    #[test]
+    fn python_comments_and_strings() {
+        let source = "x = '''multi\n# not a comment\n''' # real\ny = \"a'b\"";
+        assert_eq!(
+            vec![
+                (Region::String, "'''multi\n# not a comment\n'''"),
+                (Region::Comment, "# real"),
+                (Region::String, "\"a'b\""),
+            ],
+            regions(source, Language::Python)
+        );
+    }
+
+    #[test]
+    fn shell_comments_and_strings() {
+        assert_eq!(
+            vec![
+                (Region::String, "'it\\'"),
+                (Region::String, "\"$# \\\" #\""),
+                (Region::Comment, "# echo"),
+            ],
+            regions("echo 'it\\' \\# \"$# \\\" #\" a#b # echo", Language::Shell)
+        );
+    }
+
+    #[test]
+    fn regions_are_looked_up_by_offset() {
+        let spans = spans("a // b\n\"c\" d", Language::C);
+        assert_eq!(Region::Code, region_at(&spans, 0));
+        assert_eq!(Region::Comment, region_at(&spans, 5));
+        assert_eq!(Region::Code, region_at(&spans, 6));
+        assert_eq!(Region::String, region_at(&spans, 8));
+        assert_eq!(Region::Code, region_at(&spans, 11));
+    }
+
+    #[test]
+    fn unterminated_regions() {
+        assert_eq!(
+            vec![(Region::String, "\"open")],
+            regions("\"open\nnext", Language::C)
+        );
+        assert_eq!(
+            vec![(Region::Comment, "/* open\nnext")],
+            regions("/* open\nnext", Language::Rust)
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! # config_file
+//!
+//! Defaults for [`Config`] read from a TOML file, so that settings shared
+//! by every search don't need to be passed each time. The file is read
+//! from `$MINIGREP_CONFIG` if that is set, and otherwise from
+//! `$XDG_CONFIG_HOME/minigrep/config.toml`, where `XDG_CONFIG_HOME`
+//! defaults to `~/.config`. Keys are named after the long command-line
+//! flags:
+//!
+//! ```toml
+//! smart-case = true
+//! color = "always"
+//! ignore = ["target", "*.min.js"]
+//! ```
+//!
+//! Settings are layered: the file is applied over the built-in defaults,
+//! then the `CASE_INSENSITIVE` and `SMART_CASE` environment variables, and
+//! then the command line. Ignore globs from the command line are added to
+//! those from the file.
+
+use crate::{ColorChoice, Config, Normalization};
+use serde::Deserialize;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct Settings {
+    case_sensitive: Option<bool>,
+    smart_case: Option<bool>,
+    ignore: Option<Vec<String>>,
+    color: Option<ColorChoice>,
+    normalize: Option<Normalization>,
+    context: Option<usize>,
+}
+
+/// A config file that couldn't be read or understood.
+#[derive(Debug)]
+pub struct ConfigError {
+    pub path: PathBuf,
+    /// One-based line of the problem, where it can be pinned down
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.path.display(), line, self.message),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+/// Where the config file is looked for, and whether it was named
+/// explicitly with `MINIGREP_CONFIG`.
+pub fn path() -> Option<(PathBuf, bool)> {
+    if let Some(path) = env::var_os("MINIGREP_CONFIG") {
+        return Some((PathBuf::from(path), true));
+    }
+
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some((base.join("minigrep").join("config.toml"), false))
+}
+
+/// Apply the config file, if there is one, to `config`. A missing file is
+/// only an error if it was named with `MINIGREP_CONFIG`.
+pub fn load(config: &mut Config) -> Result<(), ConfigError> {
+    let (path, explicit) = match path() {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(source) => apply(&path, &source, config),
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !explicit => Ok(()),
+        Err(e) => Err(ConfigError {
+            path,
+            line: None,
+            message: e.to_string(),
+        }),
+    }
+}
+
+/// Apply the settings in `source`, read from `path`, to `config`.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::config_file::apply;
+/// use minigrep::Config;
+/// use std::path::Path;
+///
+/// let mut config = Config::default();
+/// apply(Path::new("config.toml"), "smart-case = true", &mut config).unwrap();
+/// assert!(config.smart_case);
+///
+/// let err = apply(Path::new("config.toml"), "\ncolour = 1", &mut config).unwrap_err();
+/// assert!(err.to_string().starts_with("config.toml:2: unknown field `colour`"));
+/// ```
+pub fn apply(path: &Path, source: &str, config: &mut Config) -> Result<(), ConfigError> {
+    let settings: Settings = toml::from_str(source).map_err(|e| ConfigError {
+        path: path.to_path_buf(),
+        line: e
+            .span()
+            .map(|span| source[..span.start].matches('\n').count() + 1),
+        message: e.message().trim().to_string(),
+    })?;
+
+    if let Some(case_sensitive) = settings.case_sensitive {
+        config.case_sensitive = case_sensitive;
+    }
+    if let Some(smart_case) = settings.smart_case {
+        config.smart_case = smart_case;
+    }
+    if let Some(ignore) = settings.ignore {
+        config.ignore = ignore;
+    }
+    if let Some(color) = settings.color {
+        config.color = color;
+    }
+    if settings.normalize.is_some() {
+        config.normalization = settings.normalize;
+    }
+    if settings.context.is_some() {
+        config.context = settings.context;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply_str(source: &str) -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+        apply(Path::new("/etc/minigrep.toml"), source, &mut config).map(|_| config)
+    }
+
+    #[test]
+    fn settings_are_applied() {
+        let config = apply_str(
+            r#"
+smart-case = true
+case-sensitive = false
+ignore = ["target", "*.lock"]
+color = "always"
+normalize = "nfkc"
+context = 4
+"#,
+        )
+        .unwrap();
+
+        assert!(config.smart_case);
+        assert!(!config.case_sensitive);
+        assert_eq!(vec!["target", "*.lock"], config.ignore);
+        assert_eq!(ColorChoice::Always, config.color);
+        assert_eq!(Some(Normalization::Nfkc), config.normalization);
+        assert_eq!(Some(4), config.context);
+    }
+
+    #[test]
+    fn missing_settings_keep_defaults() {
+        let config = apply_str("# nothing to see\n").unwrap();
+        assert!(config.case_sensitive);
+        assert_eq!(ColorChoice::Never, config.color);
+        assert!(config.ignore.is_empty());
+    }
+
+    #[test]
+    fn errors_report_file_and_line() {
+        let err = apply_str("smart-case = true\n\ncolor = \"sometimes\"\n").unwrap_err();
+        assert_eq!(Some(3), err.line);
+        assert!(err
+            .to_string()
+            .starts_with("/etc/minigrep.toml:3: unknown variant `sometimes`"));
+
+        let err = apply_str("context = [\n").unwrap_err();
+        assert!(err.line.is_some());
+    }
+
+    #[test]
+    fn environment_overrides_the_file() {
+        let mut config = apply_str("smart-case = true\n").unwrap();
+        config.query = String::from("Rust");
+        config.apply_vars(|name| name == "CASE_INSENSITIVE");
+        assert!(!config.is_case_sensitive());
+
+        let mut config = apply_str("case-sensitive = false\n").unwrap();
+        config.query = String::from("Rust");
+        config.apply_vars(|name| name == "SMART_CASE");
+        assert!(config.is_case_sensitive());
+    }
+
+    #[test]
+    fn command_line_takes_precedence() {
+        let base =
+            apply_str("smart-case = true\nignore = [\"target\"]\ncolor = \"always\"\n").unwrap();
+        let args = vec![
+            String::from("minigrep"),
+            String::from("-i"),
+            String::from("--ignore=*.log"),
+            String::from("--color"),
+            String::from("never"),
+            String::from("query"),
+            String::from("src"),
+        ];
+        let config = Config::parse(args.into_iter(), base).unwrap();
+
+        assert!(!config.smart_case);
+        assert!(!config.is_case_sensitive());
+        assert_eq!(vec!["target", "*.log"], config.ignore);
+        assert_eq!(ColorChoice::Never, config.color);
+    }
+}
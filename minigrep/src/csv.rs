@@ -0,0 +1,508 @@
+//! # csv
+//!
+//! Field-aware searching of CSV and TSV files. Records are split on a
+//! delimiter, with fields in double quotes allowed to contain delimiters,
+//! line breaks and doubled `""` quotes, and matching is restricted to the
+//! chosen columns. Columns are picked by one-based number or, using the
+//! header row, by name. The first record is always the header row.
+
+use crate::{search_folded, Config, Normalization};
+use std::borrow::Cow;
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A column to search, by one-based position or by header name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Field {
+    Index(usize),
+    Name(String),
+}
+
+impl FromStr for Field {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<usize>() {
+            Ok(0) => Err("Field numbers start at 1"),
+            Ok(n) => Ok(Field::Index(n)),
+            Err(_) if s.is_empty() => Err("Empty field name"),
+            Err(_) => Ok(Field::Name(s.to_string())),
+        }
+    }
+}
+
+/// A single record, which may span several lines if a quoted field
+/// contains line breaks.
+#[derive(Debug, PartialEq)]
+pub struct Record<'a> {
+    /// Absolute byte offset of the record from the start of the contents
+    pub offset: usize,
+    /// One-based number of the line the record starts on
+    pub line_number: usize,
+    /// The record as it appears in the contents, without its line ending
+    pub text: &'a str,
+    /// Field values, with quoting removed
+    pub fields: Vec<Cow<'a, str>>,
+}
+
+/// A record matching the query in at least one of the selected fields.
+#[derive(Debug, PartialEq)]
+pub struct FieldMatch<'a> {
+    pub record: Record<'a>,
+    /// Zero-based indices of the selected columns
+    pub columns: Vec<usize>,
+    /// Text of every match within the selected fields
+    pub matches: Vec<String>,
+}
+
+/// Split `contents` into records. Blank lines are skipped.
+///
+/// # Examples
+///
+/// ```
+/// let contents = "name,quote\nada,\"one, two\"\"\"\n";
+/// let records: Vec<_> = minigrep::csv::records(contents, ',').collect();
+///
+/// assert_eq!(2, records.len());
+/// assert_eq!("one, two\"", records[1].fields[1]);
+/// ```
+pub fn records(contents: &str, delimiter: char) -> Records<'_> {
+    Records {
+        contents,
+        delimiter,
+        pos: 0,
+        line: 1,
+    }
+}
+
+/// Iterator over the records of a CSV file, created by [`records`].
+pub struct Records<'a> {
+    contents: &'a str,
+    delimiter: char,
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Record<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.pos >= self.contents.len() {
+                return None;
+            }
+
+            let start = self.pos;
+            let line_number = self.line;
+            let mut fields = Vec::new();
+
+            loop {
+                fields.push(self.field());
+                match self.contents[self.pos..].chars().next() {
+                    Some(c) if c == self.delimiter => self.pos += c.len_utf8(),
+                    Some(_) => {
+                        self.pos += 1;
+                        self.line += 1;
+                        break;
+                    }
+                    None => break,
+                }
+            }
+
+            let text = &self.contents[start..self.pos];
+            let text = text.strip_suffix('\n').unwrap_or(text);
+            let text = text.strip_suffix('\r').unwrap_or(text);
+            if !text.is_empty() {
+                return Some(Record {
+                    offset: start,
+                    line_number,
+                    text,
+                    fields,
+                });
+            }
+        }
+    }
+}
+
+impl<'a> Records<'a> {
+    /// Read one field, leaving `pos` on the delimiter or line break that
+    /// ends it, or at the end of the contents.
+    fn field(&mut self) -> Cow<'a, str> {
+        let contents = self.contents;
+        let delimiter = self.delimiter;
+        let rest = &contents[self.pos..];
+
+        if !rest.starts_with('"') {
+            let len = rest.find([delimiter, '\n']).unwrap_or(rest.len());
+            self.pos += len;
+            let value = &rest[..len];
+            return Cow::Borrowed(value.strip_suffix('\r').unwrap_or(value));
+        }
+
+        self.pos += 1;
+        let mut value = Cow::Borrowed("");
+        loop {
+            let rest = &contents[self.pos..];
+            let (chunk, closed) = match rest.find('"') {
+                Some(i) => (&rest[..i], true),
+                None => (rest, false),
+            };
+            self.line += chunk.matches('\n').count();
+            self.pos += chunk.len();
+            append(&mut value, chunk);
+
+            if !closed {
+                return value;
+            }
+            self.pos += 1;
+            if contents[self.pos..].starts_with('"') {
+                self.pos += 1;
+                value.to_mut().push('"');
+            } else {
+                break;
+            }
+        }
+
+        // Be lenient about text between a closing quote and the delimiter
+        let rest = &contents[self.pos..];
+        let len = rest.find([delimiter, '\n']).unwrap_or(rest.len());
+        self.pos += len;
+        let trailing = &rest[..len];
+        append(&mut value, trailing.strip_suffix('\r').unwrap_or(trailing));
+        value
+    }
+}
+
+fn append<'a>(value: &mut Cow<'a, str>, chunk: &'a str) {
+    if value.is_empty() {
+        *value = Cow::Borrowed(chunk);
+    } else if !chunk.is_empty() {
+        value.to_mut().push_str(chunk);
+    }
+}
+
+/// The delimiter to use for the file at `path` when none is given: tabs
+/// for `.tsv` and `.tab` files, and commas otherwise.
+pub fn default_delimiter(path: &Path) -> char {
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some("tsv") | Some("tab") => '\t',
+        _ => ',',
+    }
+}
+
+/// Parse a delimiter given on the command line, where `\t` and `tab` both
+/// stand for a tab.
+pub fn parse_delimiter(s: &str) -> Result<char, &'static str> {
+    if s == "\\t" || s.eq_ignore_ascii_case("tab") {
+        return Ok('\t');
+    }
+
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some('"'), None) => Err("Delimiter can't be a quote"),
+        (Some(c), None) if c != '\n' && c != '\r' => Ok(c),
+        _ => Err("Delimiter must be a single character"),
+    }
+}
+
+/// Search the selected fields of every record in `contents`. The first
+/// record is taken as the header, whether or not any field is chosen by
+/// name, and is not searched itself.
+pub fn search_fields<'a>(
+    query: &str,
+    contents: &'a str,
+    delimiter: char,
+    fields: &[Field],
+    case_sensitive: bool,
+    form: Option<Normalization>,
+) -> Result<Vec<FieldMatch<'a>>, String> {
+    let results = field_matches(query, contents, delimiter, fields, case_sensitive, form)?;
+    Ok(results.flatten().collect())
+}
+
+/// The results of [`search_fields`], with `None` for each record that
+/// doesn't match, so that a caller can stop searching between records.
+fn field_matches<'a>(
+    query: &str,
+    contents: &'a str,
+    delimiter: char,
+    fields: &[Field],
+    case_sensitive: bool,
+    form: Option<Normalization>,
+) -> Result<impl Iterator<Item = Option<FieldMatch<'a>>> + 'a, String> {
+    let mut records = records(contents, delimiter);
+    let header = records.next();
+
+    let columns = fields
+        .iter()
+        .map(|field| match field {
+            Field::Index(n) => n
+                .checked_sub(1)
+                .ok_or_else(|| String::from("Field numbers start at 1")),
+            Field::Name(name) => header
+                .as_ref()
+                .and_then(|h| h.fields.iter().position(|f| f == name))
+                .ok_or_else(|| format!("No column named `{}` in the header", name)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let query = query.to_string();
+    Ok(records.map(move |record| {
+        let mut matched = query.is_empty();
+        let mut matches = Vec::new();
+
+        for &column in &columns {
+            if let Some(value) = record.fields.get(column) {
+                let found = search_folded(&query, value, !case_sensitive, form);
+                matched |= !found.is_empty();
+                matches.extend(found.iter().map(|m| m.text.to_string()));
+            }
+        }
+
+        if matched {
+            Some(FieldMatch {
+                record,
+                columns: columns.clone(),
+                matches,
+            })
+        } else {
+            None
+        }
+    }))
+}
+
+/// Join the selected fields of a record, quoting any that need it.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::csv::{records, select};
+///
+/// let record = records("1,\"Lovelace, Ada\",ada@example.com", ',').next().unwrap();
+/// assert_eq!("ada@example.com,\"Lovelace, Ada\"", select(&record, &[2, 1], ','));
+/// ```
+pub fn select(record: &Record, columns: &[usize], delimiter: char) -> String {
+    columns
+        .iter()
+        .map(|&column| {
+            let value = record.fields.get(column).map_or("", |f| f.as_ref());
+            if value.contains([delimiter, '"', '\n', '\r']) {
+                Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+            } else {
+                Cow::Borrowed(value)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(&delimiter.to_string())
+}
+
+/// The records of `contents`, read from the file at `path`, that match
+/// `config`'s query in its selected fields, as [`field_matches`] gives
+/// them, along with the delimiter they were split on.
+fn config_matches<'a>(
+    config: &Config,
+    contents: &'a str,
+    path: &Path,
+) -> Result<(impl Iterator<Item = Option<FieldMatch<'a>>> + 'a, char), String> {
+    let delimiter = config.delimiter.unwrap_or_else(|| default_delimiter(path));
+    let results = field_matches(
+        &config.query,
+        contents,
+        delimiter,
+        &config.fields,
+        config.is_case_sensitive(),
+        config.normalization,
+    )?;
+    Ok((results, delimiter))
+}
+
+/// Zero-based numbers of the lines on which the records [`render`] formats
+/// start.
+pub(crate) fn matching_lines(
+    config: &Config,
+    contents: &str,
+    path: &Path,
+) -> Result<Vec<usize>, Box<dyn Error>> {
+    let (results, _) = config_matches(config, contents, path)?;
+    Ok(results
+        .flatten()
+        .map(|result| result.record.line_number - 1)
+        .collect())
+}
+
+/// Format the records of `contents`, read from the file at `path`, that
+/// match `config`'s query in its selected fields, yielding the entries for
+/// each record in turn.
+pub fn render<'a>(
+    config: &'a Config,
+    contents: &'a str,
+    path: &Path,
+) -> Result<impl Iterator<Item = Vec<String>> + 'a, Box<dyn Error>> {
+    let (results, delimiter) = config_matches(config, contents, path)?;
+    Ok(results.map(move |result| {
+        let result = match result {
+            Some(result) => result,
+            None => return Vec::new(),
+        };
+        let prefix = if config.byte_offset {
+            format!("{}:", result.record.offset)
+        } else {
+            String::new()
+        };
+
+        if config.only_matching {
+            result
+                .matches
+                .iter()
+                .map(|text| format!("{}{}", prefix, text))
+                .collect()
+        } else if config.print_fields {
+            let selected = select(&result.record, &result.columns, delimiter);
+            vec![format!("{}{}", prefix, selected)]
+        } else {
+            vec![format!("{}{}", prefix, result.record.text)]
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PEOPLE: &str = "\
+id,name,email,notes
+1,\"Lovelace, Ada\",ada@example.com,\"wrote the \"\"first\"\" program\"
+2,Grace Hopper,grace@navy.mil,\"found a moth,
+in the relay\"\r
+3,Alan Turing,alan@example.com,
+";
+
+    #[test]
+    fn records_handle_quoting() {
+        let records: Vec<_> = records(PEOPLE, ',').collect();
+        assert_eq!(4, records.len());
+        assert_eq!("Lovelace, Ada", records[1].fields[1]);
+        assert_eq!("wrote the \"first\" program", records[1].fields[3]);
+        assert_eq!("found a moth,\nin the relay", records[2].fields[3]);
+        assert_eq!(
+            vec!["3", "Alan Turing", "alan@example.com", ""],
+            records[3].fields
+        );
+    }
+
+    #[test]
+    fn records_track_offsets_and_lines() {
+        let records: Vec<_> = records(PEOPLE, ',').collect();
+        assert_eq!(2, records[1].line_number);
+        assert_eq!(3, records[2].line_number);
+        assert_eq!(5, records[3].line_number);
+        assert_eq!(Some(records[3].offset), PEOPLE.find("3,Alan"));
+        assert!(records[2].text.ends_with("relay\""));
+    }
+
+    #[test]
+    fn fields_parse_from_numbers_and_names() {
+        assert_eq!(Ok(Field::Index(3)), "3".parse());
+        assert_eq!(Ok(Field::Name(String::from("email"))), "email".parse());
+        assert_eq!(Err("Field numbers start at 1"), "0".parse::<Field>());
+    }
+
+    #[test]
+    fn delimiters_parse() {
+        assert_eq!(Ok('\t'), parse_delimiter("\\t"));
+        assert_eq!(Ok(';'), parse_delimiter(";"));
+        assert!(parse_delimiter(";;").is_err());
+        assert_eq!('\t', default_delimiter(Path::new("export.TSV")));
+        assert_eq!('\t', default_delimiter(Path::new("dir.csv/export.tab")));
+        assert_eq!(',', default_delimiter(Path::new("export.csv")));
+    }
+
+    #[test]
+    fn search_is_restricted_to_fields() {
+        let by_name = search_fields(
+            "example",
+            PEOPLE,
+            ',',
+            &[Field::Name(String::from("email"))],
+            true,
+            None,
+        )
+        .unwrap();
+        let ids: Vec<&str> = by_name
+            .iter()
+            .map(|r| r.record.fields[0].as_ref())
+            .collect();
+        assert_eq!(vec!["1", "3"], ids);
+
+        // "moth," only appears inside the quoted notes column
+        let notes = search_fields("moth,", PEOPLE, ',', &[Field::Index(4)], true, None).unwrap();
+        assert_eq!(1, notes.len());
+        let names = search_fields("moth,", PEOPLE, ',', &[Field::Index(2)], true, None).unwrap();
+        assert!(names.is_empty());
+    }
+
+    #[test]
+    fn search_always_skips_header() {
+        let by_index = search_fields("name", PEOPLE, ',', &[Field::Index(2)], true, None).unwrap();
+        assert!(by_index.is_empty());
+
+        let by_name = search_fields(
+            "name",
+            PEOPLE,
+            ',',
+            &[Field::Name(String::from("name"))],
+            true,
+            None,
+        )
+        .unwrap();
+        assert!(by_name.is_empty());
+    }
+
+    #[test]
+    fn search_reports_unknown_columns() {
+        let result = search_fields(
+            "x",
+            PEOPLE,
+            ',',
+            &[Field::Name(String::from("phone"))],
+            true,
+            None,
+        );
+        assert_eq!(
+            Err(String::from("No column named `phone` in the header")),
+            result
+        );
+    }
+
+    #[test]
+    fn search_rejects_field_zero() {
+        let result = search_fields("x", PEOPLE, ',', &[Field::Index(0)], true, None);
+        assert_eq!(Err(String::from("Field numbers start at 1")), result);
+    }
+
+    #[test]
+    fn search_respects_case_and_collects_matches() {
+        let results = search_fields(
+            "ADA",
+            PEOPLE,
+            ',',
+            &[Field::Index(2), Field::Index(3)],
+            false,
+            None,
+        )
+        .unwrap();
+        assert_eq!(1, results.len());
+        assert_eq!(vec!["Ada", "ada"], results[0].matches);
+    }
+
+    #[test]
+    fn select_requotes_fields() {
+        let record = records(PEOPLE, ',').nth(1).unwrap();
+        assert_eq!(
+            "\"wrote the \"\"first\"\" program\"\t1",
+            select(&record, &[3, 0], '\t')
+        );
+    }
+}
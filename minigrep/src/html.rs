@@ -0,0 +1,246 @@
+//! # html
+//!
+//! A self-contained HTML report of search results, for sharing with people
+//! who would rather not read terminal output. Results are grouped by file
+//! with a match count for each, matched text is highlighted, and every
+//! matching line can be expanded to show the lines around it. All file
+//! contents are escaped as plain text, without any syntax highlighting.
+
+use crate::{wrap_matches, Match};
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::path::Path;
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em auto; max-width: 60em; color: #222; }
+h1 code, h2 { font-family: monospace; }
+h2 { font-size: 1.1em; border-bottom: 1px solid #ccc; padding-bottom: .25em; }
+.count { float: right; font-family: sans-serif; font-weight: normal; color: #666; }
+details { margin: .25em 0; }
+summary { cursor: pointer; white-space: pre-wrap; font-family: monospace; }
+pre { background: #f6f6f6; padding: .5em; margin: .25em 0 .5em 1.5em; overflow-x: auto; }
+.ln { display: inline-block; min-width: 4em; color: #888; user-select: none; }
+.hit { background: #fff8d0; }
+mark { background: #ffd84d; }
+";
+
+/// Builds a report one file at a time.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::html::Report;
+/// use std::path::Path;
+///
+/// let contents = "a <b> tag\nanother line";
+/// let matches = minigrep::search_matches("<b>", contents);
+///
+/// let mut report = Report::new("<b>", 1);
+/// report.add(Path::new("page.html"), contents, &matches);
+/// let html = report.finish();
+///
+/// assert!(html.contains("a <mark>&lt;b&gt;</mark> tag"));
+/// assert!(html.contains("1 match in 1 file"));
+/// ```
+pub struct Report {
+    query: String,
+    context: usize,
+    files: usize,
+    matches: usize,
+    sections: String,
+}
+
+impl Report {
+    /// Start a report for `query`, showing `context` lines either side of
+    /// each matching line when it is expanded.
+    pub fn new(query: &str, context: usize) -> Self {
+        Report {
+            query: query.to_string(),
+            context,
+            files: 0,
+            matches: 0,
+            sections: String::new(),
+        }
+    }
+
+    /// Add a file's matches to the report. Files without matches are left
+    /// out.
+    pub fn add(&mut self, path: &Path, contents: &str, matches: &[Match]) {
+        if matches.is_empty() {
+            return;
+        }
+
+        self.files += 1;
+        self.matches += matches.len();
+
+        let lines: Vec<&str> = crate::lines(contents).collect();
+        let s = &mut self.sections;
+        let _ = write!(
+            s,
+            "<section>\n<h2>{}<span class=\"count\">{}</span></h2>\n",
+            escape(&path.display().to_string()),
+            plural(matches.len(), "match", "matches")
+        );
+
+        for group in matches.chunk_by(|a, b| a.line_number == b.line_number) {
+            let line_number = group[0].line_number;
+            let highlighted = highlight(group);
+
+            let _ = write!(
+                s,
+                "<details>\n<summary><span class=\"ln\">{}</span>{}</summary>\n<pre>",
+                line_number, highlighted
+            );
+
+            let first = line_number.saturating_sub(self.context).max(1);
+            let last = (line_number + self.context).min(lines.len());
+            for n in first..=last {
+                if n == line_number {
+                    let _ = writeln!(
+                        s,
+                        "<span class=\"hit\"><span class=\"ln\">{}</span>{}</span>",
+                        n, highlighted
+                    );
+                } else {
+                    let _ = writeln!(s, "<span class=\"ln\">{}</span>{}", n, escape(lines[n - 1]));
+                }
+            }
+
+            s.push_str("</pre>\n</details>\n");
+        }
+
+        s.push_str("</section>\n");
+    }
+
+    /// Finish the report, returning the whole page.
+    pub fn finish(self) -> String {
+        let query = escape(&self.query);
+        format!(
+            "<!DOCTYPE html>\n\
+             <html lang=\"en\">\n\
+             <head>\n\
+             <meta charset=\"utf-8\">\n\
+             <title>minigrep: {query}</title>\n\
+             <style>\n{style}</style>\n\
+             </head>\n\
+             <body>\n\
+             <h1>Results for <code>{query}</code></h1>\n\
+             <p>{matches} in {files}</p>\n\
+             {sections}\
+             </body>\n\
+             </html>\n",
+            query = query,
+            style = STYLE,
+            matches = plural(self.matches, "match", "matches"),
+            files = plural(self.files, "file", "files"),
+            sections = self.sections,
+        )
+    }
+}
+
+/// Escape the line shared by a group of matches, wrapping each match in a
+/// `<mark>`.
+fn highlight(group: &[Match]) -> String {
+    wrap_matches(group[0].line, group, ("<mark>", "</mark>"), |text| {
+        Cow::Owned(escape(text))
+    })
+}
+
+/// Escape text for use in HTML content or attribute values.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn plural(n: usize, one: &str, many: &str) -> String {
+    format!("{} {}", n, if n == 1 { one } else { many })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{search_matches, search_normalized, Normalization};
+
+    const CONTENTS: &str = "\
+one
+two needle
+three
+four <needle> & needle
+five
+six";
+
+    fn report(context: usize) -> String {
+        let matches = search_matches("needle", CONTENTS);
+        let mut report = Report::new("needle", context);
+        report.add(Path::new("dir/<file>.txt"), CONTENTS, &matches);
+        report.add(Path::new("empty.txt"), "", &[]);
+        report.finish()
+    }
+
+    #[test]
+    fn escape_covers_markup() {
+        assert_eq!(
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;",
+            escape("<a href=\"x\">Tom & Jerry's</a>")
+        );
+    }
+
+    #[test]
+    fn report_groups_by_file_with_counts() {
+        let html = report(1);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<p>3 matches in 1 file</p>"));
+        assert!(
+            html.contains("<h2>dir/&lt;file&gt;.txt<span class=\"count\">3 matches</span></h2>")
+        );
+        assert!(!html.contains("empty.txt"));
+        assert_eq!(1, html.matches("<section>").count());
+    }
+
+    #[test]
+    fn report_highlights_every_match_on_a_line() {
+        let html = report(0);
+        assert_eq!(2, html.matches("<details>").count());
+        assert!(html.contains("four &lt;<mark>needle</mark>&gt; &amp; <mark>needle</mark>"));
+    }
+
+    #[test]
+    fn highlight_survives_overlapping_matches() {
+        // "f" matches each of the two "f"s that NFKC folds "ﬃ" into
+        let line = "o\u{fb03}ce";
+        let matches = search_normalized("f", line, Normalization::Nfkc, true);
+        assert_eq!("o<mark>\u{fb03}</mark>ce", highlight(&matches));
+
+        let overlapping: Vec<Match> = [(1, 4), (1, 4), (2, 5)]
+            .iter()
+            .map(|&(from, to)| Match {
+                offset: from,
+                text: &"abcdef"[from..to],
+                line: "abcdef",
+                line_number: 1,
+                column: from,
+            })
+            .collect();
+        assert_eq!("a<mark>bcd</mark>ef", highlight(&overlapping));
+    }
+
+    #[test]
+    fn report_shows_context_within_the_file() {
+        let html = report(2);
+        // Line 2 can only reach back to line 1, and line 4 on to line 6
+        assert!(html.contains("<pre><span class=\"ln\">1</span>one\n"));
+        assert!(html.contains("<span class=\"ln\">6</span>six\n</pre>"));
+        assert!(!html.contains("<span class=\"ln\">0</span>"));
+        assert!(!html.contains("<span class=\"ln\">7</span>"));
+    }
+}
@@ -4,16 +4,104 @@
 //! of a string of text within a target file, on case-sensitive and
 //! insensitive basis.
 
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::env;
 use std::error::Error;
 use std::fs;
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use unicode_normalization::char::canonical_combining_class;
+use unicode_normalization::UnicodeNormalization;
+
+pub mod code;
+pub mod config_file;
+pub mod csv;
+pub mod html;
+pub mod server;
+pub mod tui;
 
 /// Config arguments
 ///
-/// Indicate configuration parameters for searching text. Arguments are
-/// positional, and currently the case-sensitivity flag must be set with
-/// an environment variable, though this should be updated to take a
-/// command-line flag.
+/// Indicate configuration parameters for searching text. The query and
+/// file name are positional, and may be mixed with flags. If the file name
+/// is a directory, every file beneath it is searched and results are
+/// prefixed with the path they were found in.
+///
+/// - `-i`, `--ignore-case`: always search case-insensitively
+/// - `-s`, `--case-sensitive`: always search case-sensitively
+/// - `-S`, `--smart-case`: search case-insensitively unless the query
+///   contains an uppercase letter
+/// - `-o`, `--only-matching`: print each match on its own line, rather
+///   than the whole line containing it
+/// - `-b`, `--byte-offset`: print each match prefixed by its absolute
+///   byte offset in the file
+/// - `-E`, `--regex`: treat the query as a regular expression, in the
+///   syntax of the `regex` crate, rather than as literal text. It is
+///   matched against each line without its line ending, so `^` and `$`
+///   anchor to the start and end of lines, CRLF line endings included.
+///   Not available with `--field`
+/// - `--normalize[=FORM]`: bring the query and the file to the same
+///   Unicode normalization form, one of `nfc` (the default), `nfd`,
+///   `nfkc` or `nfkd`, before matching
+/// - `-f`, `--field FIELD`: treat the file as CSV, and only search the
+///   given columns, by one-based number or header name. May be repeated,
+///   or given a comma-separated list. The first record is always taken as
+///   the header, and is never searched
+/// - `-d`, `--delimiter CHAR`: the field delimiter, which defaults to a
+///   tab for `.tsv` files and a comma otherwise, judged for each file
+///   searched
+/// - `--print-fields`: print only the selected fields of matching records,
+///   rather than the whole record
+/// - `-l`, `--files-with-matches`: print only the names of files with
+///   matches
+/// - `-0`, `--null`: end every printed line, match or file name with a NUL
+///   character rather than a newline, for use with `xargs -0`
+/// - `--html`: print a self-contained HTML report of the results, grouped
+///   by file. Fields can't be searched for the report
+/// - `-C`, `--context NUM`: lines of context shown around each match in
+///   the HTML report, 2 by default
+/// - `--ignore GLOB`: skip files and directories matching the glob when
+///   searching a directory. May be repeated
+/// - `--color WHEN`: highlight matches, `always`, `never` (the default),
+///   or `auto` to highlight only when printing to a terminal
+/// - `--no-config`: don't read defaults from the config file
+/// - `-m`, `--max-count NUM`: stop searching a file after this many
+///   lines, or matches with `-o` or `-b`
+/// - `--max-filesize SIZE`: skip files larger than this, in bytes or with
+///   a `K`, `M` or `G` suffix
+/// - `--max-depth NUM`: don't descend more than this many directories
+///   below the one being searched
+/// - `--timeout DURATION`: stop searching after this many seconds, or with
+///   an `ms`, `s` or `m` suffix, even partway through a file
+/// - `--sort KEY`, `--sortr KEY`: order results by `path`, `modified`
+///   time or match `count`, ascending or descending respectively. Sorting
+///   by count holds back all output until every file has been searched
+/// - `-u`, `--dedup`: print each distinct line, or match with `-o`, once
+///   across all files, prefixed by the number of times it was found
+/// - `--within REGION`: only report matches in `code`, `comments` or
+///   `strings` of source files, as told apart by [`code::spans`]. May be
+///   repeated, or given a comma-separated list. Files in languages that
+///   aren't recognized are taken to be all code
+/// - `--lang LANG`: the language of the files being searched, one of
+///   `rust`, `c`, `python` or `shell`, rather than guessing from their
+///   extensions
+/// - `-I`, `--interactive`: browse results in a terminal UI, in which case
+///   the query may be left out and typed in later
+///
+/// Flags that take a value accept it either as the next argument or, for
+/// long flags, after an `=`.
+///
+/// Without a flag, the `CASE_INSENSITIVE` environment variable turns off
+/// case-sensitivity and smart-case, and the `SMART_CASE` environment
+/// variable makes smart-case the default. Explicit flags always take
+/// precedence, and the last one given wins. [`Config::load`] also reads defaults from a config
+/// file, as described in [`config_file`].
 ///
 /// # Examples
 ///
@@ -22,102 +110,1620 @@ use std::fs;
 ///     query: "the".to_string(),
 ///     filename: "poem.txt".to_string(), // path is relative to project root
 ///     case_sensitive: true,
+///     ..Default::default()
 /// };
 ///
 /// assert_eq!(config.filename, "poem.txt");
 /// ```
 ///
+#[derive(Debug)]
 pub struct Config {
     pub query: String,
     pub filename: String,
     pub case_sensitive: bool,
+    pub smart_case: bool,
+    pub only_matching: bool,
+    pub byte_offset: bool,
+    pub regex: bool,
+    pub interactive: bool,
+    pub normalization: Option<Normalization>,
+    pub fields: Vec<csv::Field>,
+    pub delimiter: Option<char>,
+    pub print_fields: bool,
+    pub files_with_matches: bool,
+    pub null: bool,
+    pub html: bool,
+    pub context: Option<usize>,
+    pub ignore: Vec<String>,
+    pub color: ColorChoice,
+    pub max_count: Option<usize>,
+    pub max_filesize: Option<u64>,
+    pub max_depth: Option<usize>,
+    pub timeout: Option<Duration>,
+    pub sort: Option<Sort>,
+    pub dedup: bool,
+    pub within: Vec<code::Region>,
+    pub language: Option<code::Language>,
 }
 
 impl Config {
-    pub fn new<I: Iterator<Item = String>>(mut args: I) -> Result<Self, &'static str> {
+    pub fn new<I: Iterator<Item = String>>(args: I) -> Result<Self, &'static str> {
+        let mut config = Config::default();
+        config.apply_env();
+        Config::parse(args, config)
+    }
+
+    /// Like [`Config::new`], but starting from the defaults in the config
+    /// file unless `--no-config` is given.
+    pub fn load<I: Iterator<Item = String>>(args: I) -> Result<Self, Box<dyn Error>> {
+        let args: Vec<String> = args.collect();
+        let no_config = args
+            .iter()
+            .skip(1)
+            .take_while(|arg| *arg != "--")
+            .any(|arg| arg == "--no-config");
+
+        let mut config = Config::default();
+        if !no_config {
+            config_file::load(&mut config)?;
+        }
+        config.apply_env();
+
+        Ok(Config::parse(args.into_iter(), config)?)
+    }
+
+    /// Apply command-line arguments on top of an existing config.
+    pub fn parse<I: Iterator<Item = String>>(
+        mut args: I,
+        mut config: Config,
+    ) -> Result<Self, &'static str> {
         args.next();
 
-        let query = match args.next() {
+        let mut positional = Vec::new();
+        let mut flags_done = false;
+
+        while let Some(arg) = args.next() {
+            if flags_done || !arg.starts_with('-') || arg == "-" {
+                positional.push(arg);
+                continue;
+            }
+
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if arg.starts_with("--") => (flag, Some(value)),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = || {
+                inline
+                    .map(String::from)
+                    .or_else(|| args.next())
+                    .ok_or("Missing value for flag")
+            };
+
+            match flag {
+                "--" => flags_done = true,
+                "-i" | "--ignore-case" => {
+                    config.case_sensitive = false;
+                    config.smart_case = false;
+                }
+                "-s" | "--case-sensitive" => {
+                    config.case_sensitive = true;
+                    config.smart_case = false;
+                }
+                "-S" | "--smart-case" => config.smart_case = true,
+                "-o" | "--only-matching" => config.only_matching = true,
+                "-b" | "--byte-offset" => config.byte_offset = true,
+                "-E" | "--regex" => config.regex = true,
+                "-I" | "--interactive" => config.interactive = true,
+                "--normalize" => {
+                    config.normalization = Some(match inline {
+                        Some(form) => form.parse()?,
+                        None => Normalization::Nfc,
+                    })
+                }
+                "-f" | "--field" => {
+                    for field in value()?.split(',') {
+                        config.fields.push(field.parse()?);
+                    }
+                }
+                "-d" | "--delimiter" => config.delimiter = Some(csv::parse_delimiter(&value()?)?),
+                "--print-fields" => config.print_fields = true,
+                "-l" | "--files-with-matches" => config.files_with_matches = true,
+                "-0" | "--null" => config.null = true,
+                "--html" => config.html = true,
+                "-C" | "--context" => {
+                    config.context = Some(value()?.parse().map_err(|_| "Context must be a number")?)
+                }
+                "--ignore" => config.ignore.push(value()?),
+                "--color" => config.color = value()?.parse()?,
+                "--no-config" => {}
+                "-m" | "--max-count" => {
+                    config.max_count =
+                        Some(value()?.parse().map_err(|_| "Max count must be a number")?)
+                }
+                "--max-filesize" => config.max_filesize = Some(parse_size(&value()?)?),
+                "--max-depth" => {
+                    config.max_depth =
+                        Some(value()?.parse().map_err(|_| "Max depth must be a number")?)
+                }
+                "--timeout" => config.timeout = Some(parse_duration(&value()?)?),
+                "--sort" | "--sortr" => {
+                    config.sort = Some(Sort {
+                        key: value()?.parse()?,
+                        descending: flag == "--sortr",
+                    })
+                }
+                "-u" | "--dedup" => config.dedup = true,
+                "--within" => {
+                    for region in value()?.split(',') {
+                        config.within.push(region.parse()?);
+                    }
+                }
+                "--lang" => config.language = Some(value()?.parse()?),
+                _ => return Err("Unrecognized flag"),
+            }
+        }
+
+        if config.dedup && (config.html || config.files_with_matches) {
+            return Err("Can't deduplicate file names or an HTML report");
+        }
+        if config.regex && !config.fields.is_empty() {
+            return Err("Can't search fields with a regular expression");
+        }
+        if config.html && !config.fields.is_empty() {
+            return Err("Can't search fields for an HTML report");
+        }
+
+        if config.interactive && positional.len() == 1 {
+            config.filename = positional.remove(0);
+            return Ok(config);
+        }
+
+        let mut positional = positional.into_iter();
+
+        config.query = match positional.next() {
             Some(arg) => arg,
             None => return Err("Didn't get a query string"),
         };
 
-        let filename = match args.next() {
+        config.filename = match positional.next() {
             Some(arg) => arg,
             None => return Err("Didn't get a file name"),
         };
 
-        let case_sensitive = env::var("CASE_INSENSITIVE").is_err();
+        if config.regex && config.pattern().is_err() {
+            return Err("Invalid regular expression");
+        }
+
+        Ok(config)
+    }
+
+    fn apply_env(&mut self) {
+        self.apply_vars(|name| env::var(name).is_ok());
+    }
+
+    /// Apply the case settings from the environment, given whether each
+    /// variable is set. They override the config file's, so
+    /// `CASE_INSENSITIVE` turns off a smart-case default from the file, as
+    /// `-i` would.
+    fn apply_vars<F: Fn(&str) -> bool>(&mut self, is_set: F) {
+        if is_set("CASE_INSENSITIVE") {
+            self.case_sensitive = false;
+            self.smart_case = false;
+        }
+        if is_set("SMART_CASE") {
+            self.smart_case = true;
+        }
+    }
+
+    /// Every match of the query in `contents`, respecting the case and
+    /// normalization settings.
+    pub fn find_matches<'a>(&self, contents: &'a str) -> Vec<Match<'a>> {
+        self.matches_by_line(contents).flatten().collect()
+    }
+
+    /// The same matches as [`Config::find_matches`], yielded a line at a
+    /// time, so that a caller can stop searching between lines.
+    pub fn matches_by_line<'a>(
+        &self,
+        contents: &'a str,
+    ) -> Box<dyn Iterator<Item = Vec<Match<'a>>> + 'a> {
+        if self.regex {
+            return match self.pattern() {
+                Ok(pattern) => Box::new(regex_lines(pattern, contents, self.normalization)),
+                Err(_) => Box::new(std::iter::empty()),
+            };
+        }
+
+        let case_sensitive = self.is_case_sensitive();
+        match self.normalization {
+            Some(form) => Box::new(folded_lines(
+                &self.query,
+                contents,
+                !case_sensitive,
+                Some(form),
+            )),
+            None if case_sensitive => Box::new(literal_lines(&self.query, contents)),
+            None => Box::new(folded_lines(&self.query, contents, true, None)),
+        }
+    }
+
+    /// Like [`Config::find_matches`], but keeping only those in the regions
+    /// of source code given by `within`, if any. `language` is that of
+    /// `contents`, where known.
+    pub fn find_code_matches<'a>(
+        &self,
+        contents: &'a str,
+        language: Option<code::Language>,
+    ) -> Vec<Match<'a>> {
+        self.code_matches_by_line(contents, language)
+            .flatten()
+            .collect()
+    }
+
+    /// The same matches as [`Config::find_code_matches`], yielded a line at
+    /// a time, so that a caller can stop searching between lines.
+    pub fn code_matches_by_line<'a>(
+        &self,
+        contents: &'a str,
+        language: Option<code::Language>,
+    ) -> Box<dyn Iterator<Item = Vec<Match<'a>>> + 'a> {
+        let lines = self.matches_by_line(contents);
+        if self.within.is_empty() {
+            return lines;
+        }
+
+        let spans = match language {
+            Some(language) => code::spans(contents, language),
+            None => Vec::new(),
+        };
+        let within = self.within.clone();
+        Box::new(lines.map(move |mut matches| {
+            matches.retain(|m| within.contains(&code::region_at(&spans, m.offset)));
+            matches
+        }))
+    }
+
+    /// The query compiled as a regular expression, respecting the case
+    /// settings, and brought to the normalization form if one is set.
+    pub fn pattern(&self) -> Result<Regex, regex::Error> {
+        let query = match self.normalization {
+            Some(form) => Cow::Owned(form.apply(&self.query)),
+            None => Cow::Borrowed(self.query.as_str()),
+        };
+        RegexBuilder::new(&query)
+            .case_insensitive(!self.is_case_sensitive())
+            .build()
+    }
+
+    /// The language of the file at `path`, as given with `--lang` or
+    /// guessed from its extension.
+    pub fn language_of(&self, path: &Path) -> Option<code::Language> {
+        self.language.or_else(|| code::Language::from_path(path))
+    }
+
+    /// Whether the search should respect case, once smart-case has been
+    /// resolved against the query.
+    pub fn is_case_sensitive(&self) -> bool {
+        if self.smart_case {
+            smart_case_sensitive(&self.query)
+        } else {
+            self.case_sensitive
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            query: String::new(),
+            filename: String::new(),
+            case_sensitive: true,
+            smart_case: false,
+            only_matching: false,
+            byte_offset: false,
+            regex: false,
+            interactive: false,
+            normalization: None,
+            fields: Vec::new(),
+            delimiter: None,
+            print_fields: false,
+            files_with_matches: false,
+            null: false,
+            html: false,
+            context: None,
+            ignore: Vec::new(),
+            color: ColorChoice::Never,
+            max_count: None,
+            max_filesize: None,
+            max_depth: None,
+            timeout: None,
+            sort: None,
+            dedup: false,
+            within: Vec::new(),
+            language: None,
+        }
+    }
+}
+
+/// Smart-case rule: a query is searched case-sensitively only if it
+/// contains at least one uppercase letter.
+///
+/// # Examples
+///
+/// ```
+/// assert!(!minigrep::smart_case_sensitive("rust"));
+/// assert!(minigrep::smart_case_sensitive("Rust"));
+/// ```
+pub fn smart_case_sensitive(query: &str) -> bool {
+    query.chars().any(char::is_uppercase)
+}
+
+pub fn run(config: Config) -> Result<Outcome, Box<dyn Error>> {
+    if config.regex {
+        config.pattern()?;
+    }
+    let deadline = config.timeout.map(|timeout| Instant::now() + timeout);
+    let root = Path::new(&config.filename);
+    let recursive = root.is_dir();
+    let mut walk = if recursive {
+        walk(root, &config.ignore, config.max_depth)?
+    } else {
+        Walk::file(root)
+    };
+    let expired = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+    let mut truncation = Truncation::default();
+    // Sorting by anything but count can be done before searching, which
+    // leaves the results free to be printed as they are found. Gathering
+    // every path first takes time of its own, so it stops at the deadline
+    let paths: Box<dyn Iterator<Item = PathBuf>> = match config.sort {
+        Some(sort) if sort.key != SortKey::Count => {
+            let found = walk.by_ref().take_while(|_| !expired()).collect();
+            let sorted = sort_paths(found, sort, deadline);
+            truncation.timed_out = expired();
+            Box::new(sorted.into_iter())
+        }
+        _ => Box::new(walk.by_ref()),
+    };
+    let by_count = config.sort.filter(|sort| sort.key == SortKey::Count);
+
+    let terminator = if config.null { '\0' } else { '\n' };
+    let color = !config.null
+        && match config.color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => io::stdout().is_terminal(),
+        };
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let mut report = html::Report::new(&config.query, config.context.unwrap_or(2));
+    let mut held = Vec::new();
+    let mut dedup = Dedup::default();
+
+    let limits = Limits {
+        // With -l, the first record found settles whether a file is listed
+        max_count: if config.files_with_matches {
+            Some(0)
+        } else {
+            config.max_count
+        },
+        deadline,
+    };
+
+    for path in paths {
+        if truncation.timed_out || expired() {
+            truncation.timed_out = true;
+            break;
+        }
+
+        if let Some(max) = config.max_filesize {
+            if fs::metadata(&path).is_ok_and(|metadata| metadata.len() > max) {
+                truncation.max_filesize.push(path);
+                continue;
+            }
+        }
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            // Binary and unreadable files are passed over inside directories
+            Err(_) if recursive => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        // Searching stops as soon as a limit is reached, and whatever was
+        // found up to then is still printed
+        let language = config.language_of(&path);
+        let found = if config.html {
+            let limits = Limits {
+                max_count: config.max_count,
+                deadline,
+            };
+            let (mut matches, stop) =
+                limits.collect(config.code_matches_by_line(&contents, language));
+            if let (Some(Stop::MaxCount), Some(max)) = (stop, config.max_count) {
+                matches.truncate(max);
+                truncation.max_count.push(path.clone());
+            }
+            truncation.timed_out = stop == Some(Stop::Deadline);
+            if by_count.is_none() {
+                report.add(&path, &contents, &matches);
+                continue;
+            }
+            Found::Report(matches.len(), contents)
+        } else {
+            let (mut records, stop) = limits.collect(render(&config, &contents, color, &path)?);
+            if let (Some(Stop::MaxCount), Some(max)) = (stop, config.max_count) {
+                if !config.files_with_matches {
+                    records.truncate(max);
+                    truncation.max_count.push(path.clone());
+                }
+            }
+            truncation.timed_out = stop == Some(Stop::Deadline);
+            Found::Records(records)
+        };
+
+        if by_count.is_some() {
+            held.push((path, found));
+        } else {
+            found.print(&config, &path, recursive, &mut report, &mut dedup, &mut out)?;
+        }
+    }
+
+    if let Some(sort) = by_count {
+        held.sort_by(|(_, a), (_, b)| sort.direct(a.count().cmp(&b.count())));
+        for (path, found) in held {
+            found.print(&config, &path, recursive, &mut report, &mut dedup, &mut out)?;
+        }
+    }
+
+    if config.html {
+        write!(out, "{}", report.finish())?;
+    }
+
+    if config.dedup {
+        let mut counts = dedup.counts;
+        if let Some(sort) = by_count {
+            counts.sort_by(|(_, a), (_, b)| sort.direct(a.cmp(b)));
+        }
+        for (record, count) in counts {
+            write!(out, "{}:{}{}", count, record, terminator)?;
+        }
+    }
+
+    out.flush()?;
+
+    truncation.max_depth = walk.too_deep;
+    if truncation.is_empty() {
+        Ok(Outcome::Complete)
+    } else {
+        Ok(Outcome::Truncated(truncation))
+    }
+}
+
+/// What was found in one file, ready to be printed.
+enum Found {
+    /// Whole contents, for the HTML report, with the number of matches
+    Report(usize, String),
+    /// Formatted lines or matches
+    Records(Vec<String>),
+}
+
+impl Found {
+    fn count(&self) -> usize {
+        match self {
+            Found::Report(count, _) => *count,
+            Found::Records(records) => records.len(),
+        }
+    }
+
+    fn print(
+        self,
+        config: &Config,
+        path: &Path,
+        recursive: bool,
+        report: &mut html::Report,
+        dedup: &mut Dedup,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        let terminator = if config.null { '\0' } else { '\n' };
+        let records = match self {
+            Found::Report(count, contents) => {
+                let limits = Limits {
+                    max_count: Some(count),
+                    deadline: None,
+                };
+                let language = config.language_of(path);
+                let (mut matches, _) =
+                    limits.collect(config.code_matches_by_line(&contents, language));
+                matches.truncate(count);
+                report.add(path, &contents, &matches);
+                return Ok(());
+            }
+            Found::Records(records) if records.is_empty() => return Ok(()),
+            Found::Records(records) => records,
+        };
+
+        if config.files_with_matches {
+            return write!(out, "{}{}", path.display(), terminator);
+        }
+
+        for record in records {
+            if config.dedup {
+                dedup.add(record);
+                continue;
+            }
+            if recursive {
+                write!(out, "{}:", path.display())?;
+            }
+            write!(out, "{}{}", record, terminator)?;
+        }
+        Ok(())
+    }
+}
+
+/// Identical records from every file, with how many times each was seen,
+/// in the order they were first seen.
+#[derive(Default)]
+struct Dedup {
+    counts: Vec<(String, usize)>,
+    index: HashMap<String, usize>,
+}
+
+impl Dedup {
+    fn add(&mut self, record: String) {
+        match self.index.get(&record) {
+            Some(&i) => self.counts[i].1 += 1,
+            None => {
+                self.index.insert(record.clone(), self.counts.len());
+                self.counts.push((record, 1));
+            }
+        }
+    }
+}
 
-        Ok(Config {
-            query,
-            filename,
-            case_sensitive,
+/// Order `paths` by name or modification time. Files whose modification
+/// time can't be read sort first, or last when descending, and ties are
+/// always broken by ascending name. Once `deadline` has passed, no more
+/// files are statted, as none of them will be searched.
+fn sort_paths(paths: Vec<PathBuf>, sort: Sort, deadline: Option<Instant>) -> Vec<PathBuf> {
+    let mut keyed: Vec<_> = paths
+        .into_iter()
+        .map(|path| {
+            let stat = sort.key == SortKey::Modified
+                && deadline.is_none_or(|deadline| Instant::now() < deadline);
+            let modified = if stat {
+                fs::metadata(&path).and_then(|m| m.modified()).ok()
+            } else {
+                None
+            };
+            (modified, path)
         })
+        .collect();
+    keyed.sort_by(|(a_time, a), (b_time, b)| match sort.key {
+        SortKey::Modified => sort.direct(a_time.cmp(b_time)).then_with(|| a.cmp(b)),
+        SortKey::Path | SortKey::Count => sort.direct(a.cmp(b)),
+    });
+    keyed.into_iter().map(|(_, path)| path).collect()
+}
+
+/// How to order results, given with `--sort` or `--sortr`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sort {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+impl Sort {
+    /// Turn an ascending comparison into one in this sort's direction,
+    /// leaving ties as they are so that a stable sort keeps their order.
+    fn direct(&self, order: Ordering) -> Ordering {
+        if self.descending {
+            order.reverse()
+        } else {
+            order
+        }
+    }
+}
+
+/// What to order results by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Path,
+    /// Modification time of each file
+    Modified,
+    /// Number of results in each file, or of each line when deduplicating
+    Count,
+}
+
+impl FromStr for SortKey {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "path" => Ok(SortKey::Path),
+            "modified" => Ok(SortKey::Modified),
+            "count" => Ok(SortKey::Count),
+            _ => Err("Sort key must be one of path, modified or count"),
+        }
+    }
+}
+
+/// How a call to [`run`] finished.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    /// Everything there was to search was searched
+    Complete,
+    /// One or more of the limits in [`Config`] cut the search short, so the
+    /// results are incomplete
+    Truncated(Truncation),
+}
+
+/// Limits on searching a single file, checked between lines.
+#[derive(Debug, Default, Clone, Copy)]
+struct Limits {
+    /// Stop once more than this many results have been found
+    max_count: Option<usize>,
+    /// Stop once this has passed
+    deadline: Option<Instant>,
+}
+
+/// Which limit stopped a search before the end of a file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stop {
+    MaxCount,
+    Deadline,
+}
+
+impl Limits {
+    /// Gather results yielded a line at a time, pulling no more lines once
+    /// a limit is reached. Reaching the maximum count means finding more
+    /// results than it allows, so that files with exactly that many aren't
+    /// reported as cut short; it is up to the caller to drop the extra.
+    fn collect<T>(&self, lines: impl Iterator<Item = Vec<T>>) -> (Vec<T>, Option<Stop>) {
+        let mut results = Vec::new();
+        for line in lines {
+            if self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                return (results, Some(Stop::Deadline));
+            }
+            results.extend(line);
+            if self.max_count.is_some_and(|max| results.len() > max) {
+                return (results, Some(Stop::MaxCount));
+            }
+        }
+        (results, None)
+    }
+}
+
+/// Which limits cut a search short, and where.
+#[derive(Debug, Default, PartialEq)]
+pub struct Truncation {
+    /// Files with more matches than the maximum count, of which only the
+    /// first were printed
+    pub max_count: Vec<PathBuf>,
+    /// Files skipped for being larger than the maximum file size
+    pub max_filesize: Vec<PathBuf>,
+    /// Directories not searched for being deeper than the maximum depth
+    pub max_depth: Vec<PathBuf>,
+    /// Whether the search ran out of time before finishing
+    pub timed_out: bool,
+}
+
+impl Truncation {
+    pub fn is_empty(&self) -> bool {
+        self.max_count.is_empty()
+            && self.max_filesize.is_empty()
+            && self.max_depth.is_empty()
+            && !self.timed_out
+    }
+
+    /// A short explanation of each limit that was hit.
+    pub fn notes(&self) -> Vec<String> {
+        let mut notes = Vec::new();
+        if !self.max_count.is_empty() {
+            notes.push(format!(
+                "stopped early in {} file(s) after reaching the maximum match count",
+                self.max_count.len()
+            ));
+        }
+        if !self.max_filesize.is_empty() {
+            notes.push(format!(
+                "skipped {} file(s) larger than the maximum file size",
+                self.max_filesize.len()
+            ));
+        }
+        if !self.max_depth.is_empty() {
+            notes.push(format!(
+                "skipped {} directory(ies) deeper than the maximum depth",
+                self.max_depth.len()
+            ));
+        }
+        if self.timed_out {
+            notes.push(String::from(
+                "the search timed out, so not every file was searched",
+            ));
+        }
+        notes
+    }
+}
+
+/// Parse a size in bytes, with an optional `K`, `M` or `G` suffix for
+/// powers of 1024.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(Ok(512), minigrep::parse_size("512"));
+/// assert_eq!(Ok(10 * 1024 * 1024), minigrep::parse_size("10M"));
+/// ```
+pub fn parse_size(s: &str) -> Result<u64, &'static str> {
+    let (digits, scale) = match s.char_indices().last() {
+        Some((i, 'k')) | Some((i, 'K')) => (&s[..i], 1 << 10),
+        Some((i, 'm')) | Some((i, 'M')) => (&s[..i], 1 << 20),
+        Some((i, 'g')) | Some((i, 'G')) => (&s[..i], 1 << 30),
+        _ => (s, 1),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(scale))
+        .ok_or("Size must be a number, optionally followed by K, M or G")
+}
+
+/// Parse a duration in seconds, which may be fractional, or with an `ms`,
+/// `s` or `m` suffix.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// assert_eq!(Ok(Duration::from_millis(1500)), minigrep::parse_duration("1.5"));
+/// assert_eq!(Ok(Duration::from_millis(250)), minigrep::parse_duration("250ms"));
+/// ```
+pub fn parse_duration(s: &str) -> Result<Duration, &'static str> {
+    let (number, scale) = if let Some(ms) = s.strip_suffix("ms") {
+        (ms, 0.001)
+    } else if let Some(secs) = s.strip_suffix('s') {
+        (secs, 1.0)
+    } else if let Some(mins) = s.strip_suffix('m') {
+        (mins, 60.0)
+    } else {
+        (s, 1.0)
+    };
+    number
+        .parse::<f64>()
+        .ok()
+        .and_then(|n| Duration::try_from_secs_f64(n * scale).ok())
+        .ok_or("Timeout must be a number of seconds, or end in ms, s or m")
+}
+
+/// Format what should be printed for the contents of the file at `path`,
+/// without line terminators, yielding the records from each line in turn.
+/// With `color`, matches are highlighted with ANSI escape codes.
+fn render<'a>(
+    config: &'a Config,
+    contents: &'a str,
+    color: bool,
+    path: &Path,
+) -> Result<Box<dyn Iterator<Item = Vec<String>> + 'a>, Box<dyn Error>> {
+    if !config.fields.is_empty() {
+        return Ok(Box::new(csv::render(config, contents, path)?));
+    }
+    let language = config.language_of(path);
+
+    let per_match = config.only_matching || config.byte_offset;
+
+    if !per_match && !config.regex && config.normalization.is_none() && config.within.is_empty() {
+        let case_sensitive = config.is_case_sensitive();
+        return Ok(Box::new(lines(contents).map(move |line| {
+            let found = if case_sensitive {
+                search(&config.query, line)
+            } else {
+                search_case_insensitive(&config.query, line)
+            };
+            found
+                .into_iter()
+                .map(|line| {
+                    if color {
+                        paint(line, &config.find_matches(line))
+                    } else {
+                        line.to_string()
+                    }
+                })
+                .collect()
+        })));
+    }
+
+    let lines = config.code_matches_by_line(contents, language);
+    Ok(Box::new(lines.map(move |matches| {
+        let mut records = Vec::new();
+        if matches.is_empty() {
+            return records;
+        }
+        if !per_match {
+            records.push(if color {
+                paint(matches[0].line, &matches)
+            } else {
+                matches[0].line.to_string()
+            });
+            return records;
+        }
+
+        for m in &matches {
+            // Patterns that can match nothing, like `x*`, have nothing to show
+            if config.only_matching && m.text.is_empty() {
+                continue;
+            }
+            let text = match (config.only_matching, color) {
+                (true, true) => format!("{}{}{}", MATCH_COLOR, m.text, RESET_COLOR),
+                (true, false) => m.text.to_string(),
+                (false, true) => paint(m.line, std::slice::from_ref(m)),
+                (false, false) => m.line.to_string(),
+            };
+            if config.byte_offset {
+                records.push(format!("{}:{}", m.offset, text));
+            } else {
+                records.push(text);
+            }
+        }
+        records
+    })))
+}
+
+/// Zero-based numbers of the lines holding what [`run`] prints for the
+/// contents of the file at `path`: every matching line, or the first line
+/// of every matching record when searching fields.
+pub(crate) fn matching_lines(
+    config: &Config,
+    contents: &str,
+    path: &Path,
+) -> Result<Vec<usize>, Box<dyn Error>> {
+    if !config.fields.is_empty() {
+        return csv::matching_lines(config, contents, path);
+    }
+
+    // Outside of fields, each line's records come in turn
+    Ok(render(config, contents, false, path)?
+        .enumerate()
+        .filter(|(_, records)| !records.is_empty())
+        .map(|(i, _)| i)
+        .collect())
+}
+
+const MATCH_COLOR: &str = "\x1b[1;31m";
+const RESET_COLOR: &str = "\x1b[0m";
+
+/// Highlight the given matches within the line they were found on.
+fn paint(line: &str, matches: &[Match]) -> String {
+    wrap_matches(line, matches, (MATCH_COLOR, RESET_COLOR), Cow::Borrowed)
+}
+
+/// Rebuild `line` with each of `matches`, which were found on it in order,
+/// placed between the `open` and `close` strings, and all of its text
+/// passed through `escape`. Folding can leave one match starting inside
+/// another in the original text, so a match starting inside one already
+/// wrapped is skipped.
+pub(crate) fn wrap_matches<'a, F>(
+    line: &'a str,
+    matches: &[Match],
+    (open, close): (&str, &str),
+    escape: F,
+) -> String
+where
+    F: Fn(&'a str) -> Cow<'a, str>,
+{
+    let mut wrapped = String::with_capacity(line.len());
+    let mut pos = 0;
+    for m in matches {
+        if m.column < pos {
+            continue;
+        }
+        let end = m.column + m.text.len();
+        wrapped.push_str(&escape(&line[pos..m.column]));
+        wrapped.push_str(open);
+        wrapped.push_str(&escape(&line[m.column..end]));
+        wrapped.push_str(close);
+        pos = end;
+    }
+    wrapped.push_str(&escape(&line[pos..]));
+    wrapped
+}
+
+/// When to highlight matches in the output.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    /// Only when printing to a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err("Color must be one of auto, always or never"),
+        }
     }
 }
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(config.filename)?;
+/// Every file under `root`, in path order. Hidden entries, whose names
+/// start with a `.`, are skipped, as are those matching any of the
+/// `ignore` globs (see [`glob_match`]). Symbolic links to directories are
+/// not followed.
+///
+/// Directories are read as the walk reaches them. Those more than
+/// `max_depth` levels below `root` are not read at all, and are collected
+/// in [`Walk::too_deep`] instead.
+pub fn walk<'a>(
+    root: &Path,
+    ignore: &'a [String],
+    max_depth: Option<usize>,
+) -> io::Result<Walk<'a>> {
+    walk_with(root, ignore, max_depth, &read_dir)
+}
+
+/// Like [`walk`], but listing each directory with `read`, which should
+/// behave as [`read_dir`] does.
+pub(crate) fn walk_with<'a>(
+    root: &Path,
+    ignore: &'a [String],
+    max_depth: Option<usize>,
+    read: &'a ReadDir<'a>,
+) -> io::Result<Walk<'a>> {
+    let mut walk = Walk {
+        root: root.to_path_buf(),
+        ignore,
+        max_depth,
+        read,
+        pending: Vec::new(),
+        too_deep: Vec::new(),
+    };
 
-    let results = if config.case_sensitive {
-        search(&config.query, &contents)
+    if max_depth == Some(0) {
+        fs::metadata(root)?;
+        walk.too_deep.push(root.to_path_buf());
     } else {
-        search_case_insensitive(&config.query, &contents)
+        let entries = walk.list(root)?;
+        walk.pending.push((entries.into_iter(), 1));
+    }
+
+    Ok(walk)
+}
+
+/// Lists a directory for a [`Walk`].
+pub(crate) type ReadDir<'a> = dyn Fn(&Path) -> io::Result<Vec<(PathBuf, bool)>> + 'a;
+
+/// The entries of `dir` that aren't hidden, as pairs of path and whether it
+/// is a directory, sorted by name. A symbolic link is never counted as a
+/// directory, so walks don't follow them.
+pub(crate) fn read_dir(dir: &Path) -> io::Result<Vec<(PathBuf, bool)>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_name().to_string_lossy().starts_with('.') {
+            entries.push((entry.path(), entry.file_type()?.is_dir()));
+        }
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+/// Iterator over files beneath a directory, created by [`walk`].
+pub struct Walk<'a> {
+    root: PathBuf,
+    ignore: &'a [String],
+    max_depth: Option<usize>,
+    read: &'a ReadDir<'a>,
+    /// Entries yet to be visited in each directory being walked, along
+    /// with their depth below the root
+    pending: Vec<(std::vec::IntoIter<(PathBuf, bool)>, usize)>,
+    /// Directories that were left out for being too deep
+    pub too_deep: Vec<PathBuf>,
+}
+
+impl<'a> Walk<'a> {
+    /// A walk over just one file.
+    pub(crate) fn file(path: &Path) -> Self {
+        Walk {
+            root: path.to_path_buf(),
+            ignore: &[],
+            max_depth: None,
+            read: &read_dir,
+            pending: vec![(vec![(path.to_path_buf(), false)].into_iter(), 0)],
+            too_deep: Vec::new(),
+        }
+    }
+
+    /// The entries of `dir` that aren't hidden or ignored, as pairs of path
+    /// and whether it is a directory, sorted by name.
+    fn list(&self, dir: &Path) -> io::Result<Vec<(PathBuf, bool)>> {
+        let mut entries = (self.read)(dir)?;
+        entries.retain(|(path, is_dir)| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let relative = path
+                .strip_prefix(&self.root)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            !self
+                .ignore
+                .iter()
+                .any(|glob| is_ignored(glob, &name, &relative, *is_dir))
+        });
+        Ok(entries)
+    }
+}
+
+impl<'a> Iterator for Walk<'a> {
+    type Item = PathBuf;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (entries, depth) = self.pending.last_mut()?;
+            let depth = *depth;
+            let (path, is_dir) = match entries.next() {
+                Some(entry) => entry,
+                None => {
+                    self.pending.pop();
+                    continue;
+                }
+            };
+
+            if !is_dir {
+                return Some(path);
+            }
+
+            if self.max_depth.is_some_and(|max| depth >= max) {
+                self.too_deep.push(path);
+            } else if let Ok(entries) = self.list(&path) {
+                // Unreadable directories are passed over
+                self.pending.push((entries.into_iter(), depth + 1));
+            }
+        }
+    }
+}
+
+/// Ignore globs ending in `/` only apply to directories, and those
+/// containing a `/` are matched against the whole path relative to the
+/// search root, rather than just the entry's name.
+fn is_ignored(glob: &str, name: &str, relative: &str, is_dir: bool) -> bool {
+    let (glob, dir_only) = match glob.strip_suffix('/') {
+        Some(glob) => (glob, true),
+        None => (glob, false),
     };
+    if dir_only && !is_dir {
+        return false;
+    }
+
+    match glob.strip_prefix('/') {
+        Some(anchored) => glob_match(anchored, relative),
+        None if glob.contains('/') => glob_match(glob, relative),
+        None => glob_match(glob, name),
+    }
+}
+
+/// Match `text` against a glob, where `?` matches any one character and
+/// `*` any run of characters, but neither matches a `/`. A `**` matches
+/// across `/` too, and `**/` may also match nothing at all.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::glob_match;
+///
+/// assert!(glob_match("*.min.js", "app.min.js"));
+/// assert!(!glob_match("*.js", "vendor/app.js"));
+/// assert!(glob_match("**/*.js", "vendor/app.js"));
+/// assert!(glob_match("**/*.js", "app.js"));
+/// ```
+pub fn glob_match(glob: &str, text: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    glob_match_chars(&glob, &text)
+}
 
-    for line in results {
-        println!("{}", line);
+fn glob_match_chars(glob: &[char], text: &[char]) -> bool {
+    match glob {
+        [] => text.is_empty(),
+        ['*', '*', '/', rest @ ..] => {
+            glob_match_chars(rest, text)
+                || (0..text.len())
+                    .filter(|&i| text[i] == '/')
+                    .any(|i| glob_match_chars(rest, &text[i + 1..]))
+        }
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|i| glob_match_chars(rest, &text[i..])),
+        ['*', rest @ ..] => {
+            let segment = text.iter().position(|&c| c == '/').unwrap_or(text.len());
+            (0..=segment).any(|i| glob_match_chars(rest, &text[i..]))
+        }
+        ['?', rest @ ..] => match text {
+            [c, tail @ ..] if *c != '/' => glob_match_chars(rest, tail),
+            _ => false,
+        },
+        [g, rest @ ..] => match text {
+            [c, tail @ ..] if c == g => glob_match_chars(rest, tail),
+            _ => false,
+        },
     }
+}
 
-    Ok(())
+/// A single occurrence of the query within the searched contents.
+#[derive(Debug, PartialEq)]
+pub struct Match<'a> {
+    /// Absolute byte offset of the match from the start of the contents
+    pub offset: usize,
+    /// The matched text, exactly as it appears in the contents
+    pub text: &'a str,
+    /// The whole line containing the match
+    pub line: &'a str,
+    /// One-based number of the line containing the match
+    pub line_number: usize,
+    /// Byte offset of the match from the start of its line
+    pub column: usize,
 }
 
 pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    contents
-        .lines()
+    lines(contents)
         .filter(|line| line.contains(query))
         .collect()
 }
 
 pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
     let query = query.to_lowercase();
-    contents
-        .lines()
+    lines(contents)
         .filter(|line| line.to_lowercase().contains(&query))
         .collect()
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Find every non-overlapping occurrence of `query`, line by line.
+///
+/// # Examples
+///
+/// ```
+/// let contents = "req-1a ok\nreq-2b failed, see req-1a";
+/// let matches = minigrep::search_matches("req-", contents);
+///
+/// let offsets: Vec<usize> = matches.iter().map(|m| m.offset).collect();
+/// assert_eq!(offsets, vec![0, 10, 29]);
+/// ```
+pub fn search_matches<'a>(query: &str, contents: &'a str) -> Vec<Match<'a>> {
+    literal_lines(query, contents).flatten().collect()
+}
 
-    #[test]
-    fn config_constructor_works() {
-        let args = vec![
-            String::from("test"),
-            String::from("the"),
-            String::from("poem.txt"),
-        ];
-        let config = Config::new(args.into_iter()).unwrap();
-        assert_eq!(config.query, "the");
-        assert_eq!(config.filename, "poem.txt");
+/// Like [`search_matches`], ignoring case. Offsets and matched text refer
+/// to the original contents, even where lowercasing changes byte lengths.
+pub fn search_matches_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<Match<'a>> {
+    search_folded(query, contents, true, None)
+}
+
+/// Like [`search_matches`], after bringing both the query and the contents
+/// to the same Unicode normalization form, so that precomposed and
+/// decomposed spellings of the same text match each other. Offsets and
+/// matched text refer to the original, unnormalized contents.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::Normalization;
+///
+/// let contents = "un cafe\u{301} noir";
+/// let matches = minigrep::search_normalized("café", contents, Normalization::Nfc, true);
+///
+/// assert_eq!(3, matches[0].offset);
+/// assert_eq!("cafe\u{301}", matches[0].text);
+/// ```
+pub fn search_normalized<'a>(
+    query: &str,
+    contents: &'a str,
+    form: Normalization,
+    case_sensitive: bool,
+) -> Vec<Match<'a>> {
+    search_folded(query, contents, !case_sensitive, Some(form))
+}
+
+/// Find every match of a regular expression, line by line, optionally after
+/// bringing the contents to a Unicode normalization form as
+/// [`search_normalized`] does. Each line is searched without its line
+/// ending, so `^` and `$` anchor to the ends of lines, including CRLF ones.
+///
+/// # Examples
+///
+/// ```
+/// use regex::Regex;
+///
+/// let contents = "GET req-7f3a ok\r\nPOST req-b01 failed, see req-7f3a\r\n";
+/// let pattern = Regex::new("req-[0-9a-f]+").unwrap();
+/// let ids: Vec<&str> = minigrep::search_regex(&pattern, contents, None)
+///     .iter()
+///     .map(|m| m.text)
+///     .collect();
+///
+/// assert_eq!(vec!["req-7f3a", "req-b01", "req-7f3a"], ids);
+/// ```
+pub fn search_regex<'a>(
+    pattern: &Regex,
+    contents: &'a str,
+    form: Option<Normalization>,
+) -> Vec<Match<'a>> {
+    regex_lines(pattern.clone(), contents, form)
+        .flatten()
+        .collect()
+}
+
+/// Unicode normalization forms that text can be brought to before
+/// matching.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Normalization {
+    /// Canonical composition
+    Nfc,
+    /// Canonical decomposition
+    Nfd,
+    /// Compatibility composition
+    Nfkc,
+    /// Compatibility decomposition
+    Nfkd,
+}
+
+impl Normalization {
+    fn apply(self, text: &str) -> String {
+        match self {
+            Normalization::Nfc => text.nfc().collect(),
+            Normalization::Nfd => text.nfd().collect(),
+            Normalization::Nfkc => text.nfkc().collect(),
+            Normalization::Nfkd => text.nfkd().collect(),
+        }
     }
+}
 
-    #[test]
-    fn config_constructor_fails_with_bad_args() {
-        let args = vec![String::from("too"), String::from("few")];
-        let config = Config::new(args.into_iter());
-        assert!(config.is_err());
-        assert_eq!(config.err(), Some("Didn't get a file name"));
+impl FromStr for Normalization {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "nfc" => Ok(Normalization::Nfc),
+            "nfd" => Ok(Normalization::Nfd),
+            "nfkc" => Ok(Normalization::Nfkc),
+            "nfkd" => Ok(Normalization::Nfkd),
+            _ => Err("Unknown normalization form"),
+        }
     }
+}
 
-    #[test]
-    fn run_can_read_file() {
+/// Search each line of `contents` after folding it, and the query, with
+/// [`fold`], mapping matches back onto the original text.
+pub(crate) fn search_folded<'a>(
+    query: &str,
+    contents: &'a str,
+    lowercase: bool,
+    form: Option<Normalization>,
+) -> Vec<Match<'a>> {
+    folded_lines(query, contents, lowercase, form)
+        .flatten()
+        .collect()
+}
+
+/// The matches of [`search_matches`], a line at a time.
+fn literal_lines<'a>(query: &str, contents: &'a str) -> impl Iterator<Item = Vec<Match<'a>>> + 'a {
+    let query = query.to_string();
+    search_lines(contents, false, None, move |line| occurrences(&query, line))
+}
+
+/// The matches of [`search_folded`], a line at a time.
+fn folded_lines<'a>(
+    query: &str,
+    contents: &'a str,
+    lowercase: bool,
+    form: Option<Normalization>,
+) -> impl Iterator<Item = Vec<Match<'a>>> + 'a {
+    let (query, _) = fold(query, lowercase, form);
+    search_lines(contents, lowercase, form, move |folded| {
+        occurrences(&query, folded)
+    })
+}
+
+/// The matches of [`search_regex`], a line at a time.
+fn regex_lines<'a>(
+    pattern: Regex,
+    contents: &'a str,
+    form: Option<Normalization>,
+) -> impl Iterator<Item = Vec<Match<'a>>> + 'a {
+    search_lines(contents, false, form, move |line| {
+        pattern
+            .find_iter(line)
+            .map(|m| (m.start(), m.end()))
+            .collect()
+    })
+}
+
+/// The byte ranges of every non-overlapping occurrence of `query` in `line`.
+fn occurrences(query: &str, line: &str) -> Vec<(usize, usize)> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    line.match_indices(query)
+        .map(|(i, text)| (i, i + text.len()))
+        .collect()
+}
+
+/// Search each line of `contents` with `find`, which returns the byte
+/// ranges it matched, yielding the matches of each line in turn. Lines are
+/// first folded with [`fold`] if `lowercase` or `form` call for it, and the
+/// ranges mapped back onto the original.
+fn search_lines<'a, F>(
+    contents: &'a str,
+    lowercase: bool,
+    form: Option<Normalization>,
+    mut find: F,
+) -> impl Iterator<Item = Vec<Match<'a>>> + 'a
+where
+    F: FnMut(&str) -> Vec<(usize, usize)> + 'a,
+{
+    let folding = lowercase || form.is_some();
+    lines_with_offsets(contents)
+        .enumerate()
+        .map(move |(n, (start, line))| {
+            let (folded, spans) = if folding {
+                fold(line, lowercase, form)
+            } else {
+                (String::new(), Vec::new())
+            };
+
+            let mut matches: Vec<Match> = Vec::new();
+            for (i, j) in find(if folding { &folded } else { line }) {
+                let (from, to) = if !folding {
+                    (i, j)
+                } else {
+                    let from = spans.get(i).map_or(line.len(), |span| span.0);
+                    let to = if j > i { spans[j - 1].1 } else { from };
+                    (from, to)
+                };
+
+                // Folding can expand one original character into several,
+                // as NFKC does "ﬃ" into "ffi", so that separate matches in
+                // the folded line map back onto the same stretch of the
+                // original. Those are merged, keeping matches disjoint.
+                if let Some(last) = matches.last_mut() {
+                    let end = last.column + last.text.len();
+                    if from < end {
+                        last.text = &line[last.column..end.max(to)];
+                        continue;
+                    }
+                }
+
+                matches.push(Match {
+                    offset: start + from,
+                    text: &line[from..to],
+                    line,
+                    line_number: n + 1,
+                    column: from,
+                });
+            }
+            matches
+        })
+}
+
+/// Split contents into lines, like [`str::lines`] but also treating a
+/// trailing `\r` at the very end as part of a CRLF line ending.
+pub(crate) fn lines(contents: &str) -> impl Iterator<Item = &str> {
+    lines_with_offsets(contents).map(|(_, line)| line)
+}
+
+/// Split contents into lines as [`lines`] does, along with the byte offset
+/// at which each line starts.
+fn lines_with_offsets(contents: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut offset = 0;
+    contents.split_inclusive('\n').map(move |raw| {
+        let start = offset;
+        offset += raw.len();
+        let line = raw.strip_suffix('\n').unwrap_or(raw);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        (start, line)
+    })
+}
+
+/// Normalize and/or lowercase `text`, recording for every byte of the
+/// result the span of original text it came from.
+///
+/// Normalization is applied to runs of a starter character followed by
+/// any combining marks, which is what keeps the spans small. Compositions
+/// across two starters, like conjoining Hangul jamo, are not made.
+fn fold(text: &str, lowercase: bool, form: Option<Normalization>) -> (String, Vec<(usize, usize)>) {
+    let mut folded = String::with_capacity(text.len());
+    let mut spans = Vec::with_capacity(text.len());
+
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for (i, c) in text.char_indices().skip(1) {
+        if form.is_none() || canonical_combining_class(c) == 0 {
+            segments.push((start, i));
+            start = i;
+        }
+    }
+    if !text.is_empty() {
+        segments.push((start, text.len()));
+    }
+
+    for (from, to) in segments {
+        let segment = &text[from..to];
+        let normalized = match form {
+            Some(form) => Cow::Owned(form.apply(segment)),
+            None => Cow::Borrowed(segment),
+        };
+        if lowercase {
+            folded.push_str(&normalized.to_lowercase());
+        } else {
+            folded.push_str(&normalized);
+        }
+        spans.resize(folded.len(), (from, to));
+    }
+
+    (folded, spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rendered(config: &Config, contents: &str, color: bool, path: &str) -> Vec<String> {
+        render(config, contents, color, Path::new(path))
+            .unwrap()
+            .flatten()
+            .collect()
+    }
+
+    #[test]
+    fn config_constructor_works() {
+        let args = vec![
+            String::from("test"),
+            String::from("the"),
+            String::from("poem.txt"),
+        ];
+        let config = Config::new(args.into_iter()).unwrap();
+        assert_eq!(config.query, "the");
+        assert_eq!(config.filename, "poem.txt");
+    }
+
+    #[test]
+    fn config_constructor_fails_with_bad_args() {
+        let args = vec![String::from("too"), String::from("few")];
+        let config = Config::new(args.into_iter());
+        assert!(config.is_err());
+        assert_eq!(config.err(), Some("Didn't get a file name"));
+    }
+
+    #[test]
+    fn config_constructor_accepts_flags_anywhere() {
+        let args = vec![
+            String::from("test"),
+            String::from("the"),
+            String::from("-i"),
+            String::from("poem.txt"),
+        ];
+        let config = Config::new(args.into_iter()).unwrap();
+        assert_eq!(config.query, "the");
+        assert_eq!(config.filename, "poem.txt");
+        assert!(!config.case_sensitive);
+    }
+
+    #[test]
+    fn config_constructor_parses_color() {
+        let args = vec![
+            String::from("test"),
+            String::from("--color=always"),
+            String::from("the"),
+            String::from("poem.txt"),
+        ];
+        let config = Config::new(args.into_iter()).unwrap();
+        assert_eq!(ColorChoice::Always, config.color);
+
+        let args = vec![
+            String::from("test"),
+            String::from("--color=sometimes"),
+            String::from("the"),
+            String::from("poem.txt"),
+        ];
+        assert!(Config::new(args.into_iter()).is_err());
+    }
+
+    #[test]
+    fn config_constructor_fails_with_unknown_flag() {
+        let args = vec![
+            String::from("test"),
+            String::from("--nope"),
+            String::from("the"),
+            String::from("poem.txt"),
+        ];
+        let config = Config::new(args.into_iter());
+        assert_eq!(config.err(), Some("Unrecognized flag"));
+    }
+
+    #[test]
+    fn interactive_config_needs_only_a_file_name() {
+        let args = vec![
+            String::from("test"),
+            String::from("--interactive"),
+            String::from("poem.txt"),
+        ];
+        let config = Config::new(args.into_iter()).unwrap();
+        assert!(config.interactive);
+        assert_eq!(config.query, "");
+        assert_eq!(config.filename, "poem.txt");
+    }
+
+    #[test]
+    fn config_constructor_parses_flag_values() {
+        let args = vec![
+            String::from("test"),
+            String::from("--field"),
+            String::from("email,2"),
+            String::from("-f"),
+            String::from("notes"),
+            String::from("--delimiter=;"),
+            String::from("example"),
+            String::from("people.csv"),
+        ];
+        let config = Config::new(args.into_iter()).unwrap();
+        assert_eq!(
+            vec![
+                csv::Field::Name(String::from("email")),
+                csv::Field::Index(2),
+                csv::Field::Name(String::from("notes")),
+            ],
+            config.fields
+        );
+        assert_eq!(Some(';'), config.delimiter);
+        assert_eq!(config.query, "example");
+        assert_eq!(config.filename, "people.csv");
+
+        let args = vec![String::from("test"), String::from("--field")];
+        let config = Config::new(args.into_iter());
+        assert_eq!(config.err(), Some("Missing value for flag"));
+    }
+
+    #[test]
+    fn explicit_case_flags_override_smart_case() {
+        let args = vec![
+            String::from("test"),
+            String::from("-S"),
+            String::from("-s"),
+            String::from("the"),
+            String::from("poem.txt"),
+        ];
+        let config = Config::new(args.into_iter()).unwrap();
+        assert!(!config.smart_case);
+        assert!(config.is_case_sensitive());
+    }
+
+    #[test]
+    fn smart_case_depends_on_query() {
+        let mut config = Config {
+            query: String::from("rust"),
+            case_sensitive: true,
+            smart_case: true,
+            ..Default::default()
+        };
+        assert!(!config.is_case_sensitive());
+
+        config.query = String::from("Rust");
+        assert!(config.is_case_sensitive());
+    }
+
+    #[test]
+    fn run_can_read_file() {
         let config = Config {
             query: String::from("the"),
             filename: String::from("poem.txt"),
             case_sensitive: true,
+            ..Default::default()
         };
         assert!(run(config).is_ok());
     }
@@ -128,6 +1734,7 @@ mod tests {
             query: String::from("fail"),
             filename: String::from("fail.txt"),
             case_sensitive: true,
+            ..Default::default()
         };
         assert!(run(config).is_err());
     }
@@ -156,4 +1763,589 @@ Trust me.";
             search_case_insensitive(query, contents)
         );
     }
+
+    #[test]
+    fn search_matches_reports_absolute_offsets() {
+        let contents = "\
+id req-7f ok
+req-a0 and req-b1";
+        let matches = search_matches("req-", contents);
+        let found: Vec<(usize, &str)> = matches.iter().map(|m| (m.offset, m.text)).collect();
+        assert_eq!(vec![(3, "req-"), (13, "req-"), (24, "req-")], found);
+        assert_eq!("req-a0 and req-b1", matches[2].line);
+    }
+
+    #[test]
+    fn search_matches_handles_crlf_offsets() {
+        let contents = "one\r\ntwo\r\n";
+        let matches = search_matches("two", contents);
+        assert_eq!(5, matches[0].offset);
+        assert_eq!("two", matches[0].line);
+    }
+
+    #[test]
+    fn search_matches_case_insensitive_keeps_original_text() {
+        let contents = "Trust the RUST compiler";
+        let matches = search_matches_case_insensitive("rUsT", contents);
+        let found: Vec<(usize, &str)> = matches.iter().map(|m| (m.offset, m.text)).collect();
+        assert_eq!(vec![(1, "rust"), (10, "RUST")], found);
+    }
+
+    #[test]
+    fn search_matches_case_insensitive_maps_changed_lengths() {
+        // 'İ' lowercases to two characters, shifting everything after it
+        let contents = "İstanbul, KELVIN";
+        let matches = search_matches_case_insensitive("kelvin", contents);
+        assert_eq!(1, matches.len());
+        assert_eq!("KELVIN", matches[0].text);
+        assert_eq!(contents.find("KELVIN"), Some(matches[0].offset));
+    }
+
+    #[test]
+    fn search_matches_ignores_empty_query() {
+        assert!(search_matches("", "anything").is_empty());
+        assert!(search_matches_case_insensitive("", "anything").is_empty());
+    }
+
+    #[test]
+    fn regex_search_extracts_ids() {
+        let contents = "id req-7f ok\r\nreq-a0 and req-b1\r\nrequest";
+        let pattern = Regex::new("req-[0-9a-f]+").unwrap();
+        let found: Vec<(usize, &str)> = search_regex(&pattern, contents, None)
+            .iter()
+            .map(|m| (m.offset, m.text))
+            .collect();
+        assert_eq!(vec![(3, "req-7f"), (14, "req-a0"), (25, "req-b1")], found);
+
+        let config = Config::new(args(&["-o", "-b", "-E", "req-[0-9a-f]+", "log"])).unwrap();
+        assert_eq!(
+            vec!["3:req-7f", "14:req-a0", "25:req-b1"],
+            rendered(&config, contents, false, "data.txt")
+        );
+
+        // Whole lines, with matches painted, and case folded by the regex
+        let config = Config::new(args(&["-E", "-i", "^REQ-\\w+", "log"])).unwrap();
+        assert_eq!(
+            vec!["\x1b[1;31mreq-a0\x1b[0m and req-b1"],
+            rendered(&config, contents, true, "data.txt")
+        );
+    }
+
+    #[test]
+    fn regex_search_with_normalization() {
+        let contents = "un cafe\u{301} noir";
+        let pattern = Regex::new("caf\u{e9}|noir$").unwrap();
+        let found: Vec<&str> = search_regex(&pattern, contents, Some(Normalization::Nfc))
+            .iter()
+            .map(|m| m.text)
+            .collect();
+        assert_eq!(vec!["cafe\u{301}", "noir"], found);
+    }
+
+    #[test]
+    fn fields_split_on_each_files_delimiter() {
+        let config = Config::new(args(&["-f", "2", "b", "exports"])).unwrap();
+        let contents = "one\ttwo\na\tb\n";
+        assert_eq!(
+            vec!["a\tb"],
+            rendered(&config, contents, false, "exports/data.tsv")
+        );
+        assert!(rendered(&config, contents, false, "exports/data.csv").is_empty());
+
+        assert_eq!(
+            Config::new(args(&["--html", "-f", "2", "b", "exports"])).err(),
+            Some("Can't search fields for an HTML report")
+        );
+    }
+
+    #[test]
+    fn regex_flag_is_validated() {
+        assert_eq!(
+            Config::new(args(&["-E", "req-[", "log"])).err(),
+            Some("Invalid regular expression")
+        );
+        assert_eq!(
+            Config::new(args(&["-E", "-f", "2", "x", "data.csv"])).err(),
+            Some("Can't search fields with a regular expression")
+        );
+
+        // Empty matches aren't printed on their own
+        let config = Config::new(args(&["-o", "-E", "x*", "log"])).unwrap();
+        assert_eq!(vec!["xx"], rendered(&config, "a\nxx", false, "data.txt"));
+    }
+
+    #[test]
+    fn config_constructor_parses_normalization() {
+        let args = vec![
+            String::from("test"),
+            String::from("--normalize=NFKD"),
+            String::from("café"),
+            String::from("poem.txt"),
+        ];
+        let config = Config::new(args.into_iter()).unwrap();
+        assert_eq!(Some(Normalization::Nfkd), config.normalization);
+
+        let args = vec![
+            String::from("test"),
+            String::from("--normalize=nfx"),
+            String::from("café"),
+            String::from("poem.txt"),
+        ];
+        let config = Config::new(args.into_iter());
+        assert_eq!(config.err(), Some("Unknown normalization form"));
+    }
+
+    #[test]
+    fn raw_search_misses_other_normalization() {
+        let contents = "cafe\u{301} au lait";
+        assert!(search("caf\u{e9}", contents).is_empty());
+    }
+
+    #[test]
+    fn normalized_search_matches_either_spelling() {
+        let decomposed = "un cafe\u{301}, deux caf\u{e9}s";
+        for form in [Normalization::Nfc, Normalization::Nfd] {
+            let matches = search_normalized("caf\u{e9}", decomposed, form, true);
+            let found: Vec<(usize, &str)> = matches.iter().map(|m| (m.offset, m.text)).collect();
+            assert_eq!(vec![(3, "cafe\u{301}"), (16, "caf\u{e9}")], found);
+        }
+    }
+
+    #[test]
+    fn normalized_search_with_compatibility_forms() {
+        // The "ﬁ" ligature only equals "fi" under compatibility forms
+        let contents = "a \u{fb01}ne day";
+        assert!(search_normalized("fine", contents, Normalization::Nfc, true).is_empty());
+
+        let matches = search_normalized("fine", contents, Normalization::Nfkc, true);
+        assert_eq!(2, matches[0].offset);
+        assert_eq!("\u{fb01}ne", matches[0].text);
+    }
+
+    #[test]
+    fn matches_within_one_ligature_are_merged() {
+        // "ﬃ" folds to "ffi", so "f" matches twice within it, and "ff" then
+        // "fi" would overlap there too
+        let contents = "o\u{fb03}ce \u{fb03}";
+        let matches = search_normalized("f", contents, Normalization::Nfkc, true);
+        let found: Vec<(usize, &str)> = matches.iter().map(|m| (m.column, m.text)).collect();
+        assert_eq!(vec![(1, "\u{fb03}"), (7, "\u{fb03}")], found);
+
+        let pattern = Regex::new("ff|fi").unwrap();
+        let matches = search_regex(&pattern, contents, Some(Normalization::Nfkc));
+        assert_eq!(2, matches.len());
+        assert_eq!("\u{fb03}", matches[0].text);
+    }
+
+    #[test]
+    fn normalized_search_can_ignore_case() {
+        let contents = "CAFE\u{301}";
+        let matches = search_normalized("caf\u{e9}", contents, Normalization::Nfc, false);
+        assert_eq!(contents, matches[0].text);
+    }
+
+    #[test]
+    fn search_strips_crlf_line_endings() {
+        let contents = "Rust:\r\nsafe, fast, productive.\r\nTrust me.\r";
+        assert_eq!(vec!["Rust:", "Trust me."], search("ust", contents));
+        assert_eq!(
+            vec!["Rust:", "Trust me."],
+            search_case_insensitive("UST", contents)
+        );
+
+        let matches = search_matches("me.", contents);
+        assert_eq!("Trust me.", matches[0].line);
+    }
+
+    #[test]
+    fn crlf_lines_match_end_anchors() {
+        let contents = "Rust:\r\nsafe, fast, productive.\r\nTrust me.\r";
+        let config = Config::new(args(&["-E", "(:|\\.)$", "poem.txt"])).unwrap();
+        assert_eq!(
+            vec!["Rust:", "safe, fast, productive.", "Trust me."],
+            rendered(&config, contents, false, "data.txt")
+        );
+
+        let config = Config::new(args(&["-o", "-E", "\\w+$", "poem.txt"])).unwrap();
+        assert!(rendered(&config, contents, false, "data.txt").is_empty());
+        let config = Config::new(args(&["-o", "-E", "\\w+\\.$", "poem.txt"])).unwrap();
+        assert_eq!(
+            vec!["productive.", "me."],
+            rendered(&config, contents, false, "data.txt")
+        );
+    }
+
+    #[test]
+    fn render_formats_each_mode() {
+        let contents = "one two\r\ntwo three two\r\n";
+        let mut config = Config {
+            query: String::from("two"),
+            ..Default::default()
+        };
+        assert_eq!(
+            vec!["one two", "two three two"],
+            rendered(&config, contents, false, "data.txt")
+        );
+
+        config.only_matching = true;
+        config.byte_offset = true;
+        assert_eq!(
+            vec!["4:two", "9:two", "19:two"],
+            rendered(&config, contents, false, "data.txt")
+        );
+
+        config.only_matching = false;
+        config.byte_offset = false;
+        config.normalization = Some(Normalization::Nfc);
+        assert_eq!(
+            vec!["one two", "two three two"],
+            rendered(&config, contents, false, "data.txt")
+        );
+    }
+
+    #[test]
+    fn walk_lists_files_in_order() {
+        let root = env::temp_dir().join(format!("minigrep-walk-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("b/.git")).unwrap();
+        fs::write(root.join("c.txt"), "").unwrap();
+        fs::write(root.join("b/a.txt"), "").unwrap();
+        fs::write(root.join("b/.git/config"), "").unwrap();
+        fs::write(root.join(".env"), "").unwrap();
+
+        let files = walk(&root, &[], None).map(Walk::collect::<Vec<_>>);
+        let ignored = walk(&root, &[String::from("b/")], None).map(Walk::collect::<Vec<_>>);
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(
+            vec![root.join("b/a.txt"), root.join("c.txt")],
+            files.unwrap()
+        );
+        assert_eq!(vec![root.join("c.txt")], ignored.unwrap());
+    }
+
+    #[test]
+    fn walk_stops_at_max_depth() {
+        let root = env::temp_dir().join(format!("minigrep-depth-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("a/b/c")).unwrap();
+        fs::write(root.join("top.txt"), "").unwrap();
+        fs::write(root.join("a/mid.txt"), "").unwrap();
+        fs::write(root.join("a/b/c/deep.txt"), "").unwrap();
+
+        let mut shallow = walk(&root, &[], Some(1)).unwrap();
+        let files: Vec<_> = shallow.by_ref().collect();
+        let mut none = walk(&root, &[], Some(0)).unwrap();
+        let nothing: Vec<_> = none.by_ref().collect();
+        let everything: Vec<_> = walk(&root, &[], None).unwrap().collect();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(vec![root.join("top.txt")], files);
+        assert_eq!(vec![root.join("a")], shallow.too_deep);
+        assert!(nothing.is_empty());
+        assert_eq!(vec![root.clone()], none.too_deep);
+        assert_eq!(
+            vec![
+                root.join("a/b/c/deep.txt"),
+                root.join("a/mid.txt"),
+                root.join("top.txt")
+            ],
+            everything
+        );
+    }
+
+    #[test]
+    fn run_reports_truncation() {
+        let config = Config {
+            query: String::from("o"),
+            filename: String::from("poem.txt"),
+            max_count: Some(1),
+            ..Default::default()
+        };
+        let outcome = run(config).unwrap();
+        assert_eq!(
+            Outcome::Truncated(Truncation {
+                max_count: vec![PathBuf::from("poem.txt")],
+                ..Default::default()
+            }),
+            outcome
+        );
+
+        let config = Config {
+            query: String::from("o"),
+            filename: String::from("poem.txt"),
+            max_filesize: Some(10),
+            ..Default::default()
+        };
+        match run(config).unwrap() {
+            Outcome::Truncated(truncation) => {
+                assert_eq!(vec![PathBuf::from("poem.txt")], truncation.max_filesize);
+                assert_eq!(1, truncation.notes().len());
+            }
+            Outcome::Complete => panic!("expected the file to be skipped"),
+        }
+
+        let config = Config {
+            query: String::from("o"),
+            filename: String::from("poem.txt"),
+            timeout: Some(Duration::ZERO),
+            ..Default::default()
+        };
+        assert!(matches!(run(config).unwrap(), Outcome::Truncated(t) if t.timed_out));
+    }
+
+    #[test]
+    fn limits_stop_pulling_lines() {
+        // Endless input shows that no more lines are pulled once a limit
+        // is reached
+        let limits = Limits {
+            max_count: Some(3),
+            deadline: None,
+        };
+        let endless = (0..).map(|n| vec![n; n % 2]);
+        assert_eq!(
+            (vec![1, 3, 5, 7], Some(Stop::MaxCount)),
+            limits.collect(endless)
+        );
+        assert_eq!(
+            (vec![1, 3], None),
+            limits.collect((0..4).map(|n| vec![n; n % 2]))
+        );
+
+        let expired = Limits {
+            max_count: None,
+            deadline: Some(Instant::now()),
+        };
+        assert_eq!(
+            (Vec::<usize>::new(), Some(Stop::Deadline)),
+            expired.collect((0..).map(|n| vec![n]))
+        );
+
+        let config = Config::new(args(&["-o", "a", "data.txt"])).unwrap();
+        let contents = "a a\nb\na a a\n".repeat(1000);
+        // Searching stops after the third line, which takes it past three
+        let lines = render(&config, &contents, false, Path::new("data.txt")).unwrap();
+        assert_eq!(5, limits.collect(lines).0.len());
+    }
+
+    #[test]
+    fn run_completes_within_limits() {
+        let config = Config {
+            query: String::from("frog"),
+            filename: String::from("poem.txt"),
+            max_count: Some(1),
+            max_filesize: Some(1 << 20),
+            timeout: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        assert_eq!(Outcome::Complete, run(config).unwrap());
+    }
+
+    #[test]
+    fn limits_parse() {
+        assert_eq!(Ok(2048), parse_size("2k"));
+        assert!(parse_size("lots").is_err());
+        assert_eq!(Ok(Duration::from_secs(120)), parse_duration("2m"));
+        assert_eq!(Ok(Duration::from_secs(3)), parse_duration("3s"));
+        assert!(parse_duration("-1").is_err());
+    }
+
+    fn args(list: &[&str]) -> impl Iterator<Item = String> {
+        let mut args = vec![String::from("test")];
+        args.extend(list.iter().map(|arg| arg.to_string()));
+        args.into_iter()
+    }
+
+    #[test]
+    fn sort_and_dedup_flags() {
+        let config = Config::new(args(&["--sortr=count", "-u", "the", "poem.txt"])).unwrap();
+        assert_eq!(
+            Some(Sort {
+                key: SortKey::Count,
+                descending: true
+            }),
+            config.sort
+        );
+        assert!(config.dedup);
+
+        let config = Config::new(args(&["--sort", "modified", "the", "poem.txt"])).unwrap();
+        assert_eq!(Some(SortKey::Modified), config.sort.map(|sort| sort.key));
+        assert_eq!(Some(false), config.sort.map(|sort| sort.descending));
+
+        assert!(Config::new(args(&["--sort", "size", "the", "poem.txt"])).is_err());
+        assert!(Config::new(args(&["-u", "-l", "the", "poem.txt"])).is_err());
+    }
+
+    #[test]
+    fn paths_sort_by_name_or_modification_time() {
+        let root = env::temp_dir().join(format!("minigrep-sort-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let now = std::time::SystemTime::now();
+        for (name, age) in [("a.txt", 10), ("b.txt", 30), ("c.txt", 20)] {
+            let file = fs::File::create(root.join(name)).unwrap();
+            file.set_modified(now - Duration::from_secs(age)).unwrap();
+        }
+        let paths = vec![root.join("c.txt"), root.join("a.txt"), root.join("b.txt")];
+
+        let by_name = sort_paths(
+            paths.clone(),
+            Sort {
+                key: SortKey::Path,
+                descending: true,
+            },
+            None,
+        );
+        let by_time = sort_paths(
+            paths.clone(),
+            Sort {
+                key: SortKey::Modified,
+                descending: false,
+            },
+            None,
+        );
+        let file = fs::File::options()
+            .write(true)
+            .open(root.join("b.txt"))
+            .unwrap();
+        file.set_modified(now - Duration::from_secs(10)).unwrap();
+        let newest_first = sort_paths(
+            paths,
+            Sort {
+                key: SortKey::Modified,
+                descending: true,
+            },
+            None,
+        );
+        fs::remove_dir_all(&root).unwrap();
+
+        let names = |paths: Vec<PathBuf>| -> Vec<String> {
+            paths
+                .iter()
+                .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect()
+        };
+        assert_eq!(vec!["c.txt", "b.txt", "a.txt"], names(by_name));
+        assert_eq!(vec!["b.txt", "c.txt", "a.txt"], names(by_time));
+        // a.txt and b.txt now tie, and stay in name order
+        assert_eq!(vec!["a.txt", "b.txt", "c.txt"], names(newest_first));
+    }
+
+    #[test]
+    fn descending_sorts_keep_ties_in_order() {
+        let descending = Sort {
+            key: SortKey::Count,
+            descending: true,
+        };
+        let mut counts = vec![("a", 1), ("b", 2), ("c", 1), ("d", 2)];
+        counts.sort_by(|(_, a), (_, b)| descending.direct(a.cmp(b)));
+        assert_eq!(vec![("b", 2), ("d", 2), ("a", 1), ("c", 1)], counts);
+    }
+
+    #[test]
+    fn dedup_counts_in_first_seen_order() {
+        let mut dedup = Dedup::default();
+        for record in ["fn main() {", "}", "fn main() {", "}", "}"] {
+            dedup.add(String::from(record));
+        }
+        assert_eq!(
+            vec![(String::from("fn main() {"), 2), (String::from("}"), 3)],
+            dedup.counts
+        );
+    }
+
+    #[test]
+    fn within_filters_matches_by_region() {
+        let contents = "let name = \"name\"; // name\nprint(name)";
+        let mut config = Config::new(args(&["--within", "code", "name", "src"])).unwrap();
+        let rust = Some(code::Language::Rust);
+
+        let offsets = |config: &Config, language| -> Vec<usize> {
+            config
+                .find_code_matches(contents, language)
+                .iter()
+                .map(|m| m.offset)
+                .collect()
+        };
+        assert_eq!(vec![4, 33], offsets(&config, rust));
+        // Without a language everything is code
+        assert_eq!(vec![4, 12, 22, 33], offsets(&config, None));
+
+        config.within = vec![code::Region::Comment, code::Region::String];
+        assert_eq!(vec![12, 22], offsets(&config, rust));
+        assert!(offsets(&config, None).is_empty());
+        assert_eq!(
+            vec!["let name = \"name\"; // name"],
+            rendered(&config, contents, false, "main.rs")
+        );
+
+        let config = Config::new(args(&["--within=strings", "--lang=python", "q", "x"])).unwrap();
+        assert_eq!(vec![code::Region::String], config.within);
+        assert_eq!(
+            Some(code::Language::Python),
+            config.language_of(Path::new("x.rs"))
+        );
+        assert!(Config::new(args(&["--within", "docs", "q", "x"])).is_err());
+    }
+
+    #[test]
+    fn ignore_globs_match_names_or_paths() {
+        assert!(is_ignored("*.lock", "Cargo.lock", "Cargo.lock", false));
+        assert!(is_ignored("*.lock", "Cargo.lock", "sub/Cargo.lock", false));
+        assert!(!is_ignored(
+            "/*.lock",
+            "Cargo.lock",
+            "sub/Cargo.lock",
+            false
+        ));
+        assert!(is_ignored("target/", "target", "target", true));
+        assert!(!is_ignored("target/", "target", "target", false));
+        assert!(is_ignored("src/**/gen", "gen", "src/a/b/gen", true));
+    }
+
+    #[test]
+    fn glob_match_handles_wildcards() {
+        assert!(glob_match("a?c", "abc"));
+        assert!(!glob_match("a?c", "a/c"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("*", "a/b"));
+        assert!(glob_match("**", "a/b"));
+        assert!(glob_match("src/**/mod.rs", "src/mod.rs"));
+        assert!(glob_match("src/**/mod.rs", "src/a/b/mod.rs"));
+        assert!(!glob_match("src/**/mod.rs", "src/a/b/lib.rs"));
+    }
+
+    #[test]
+    fn render_paints_matches() {
+        let config = Config {
+            query: String::from("two"),
+            ..Default::default()
+        };
+        assert_eq!(
+            vec!["one \x1b[1;31mtwo\x1b[0m, \x1b[1;31mtwo\x1b[0m"],
+            rendered(&config, "one two, two\nthree", true, "data.txt")
+        );
+    }
+
+    #[test]
+    fn paint_survives_overlapping_matches() {
+        // "f" matches each of the two "f"s that NFKC folds "ﬃ" into
+        let config = Config::new(args(&["--normalize=nfkc", "f", "office.txt"])).unwrap();
+        assert_eq!(
+            vec!["o\x1b[1;31m\u{fb03}\x1b[0mce"],
+            rendered(&config, "o\u{fb03}ce", true, "data.txt")
+        );
+
+        let line = "abcdef";
+        let overlapping: Vec<Match> = [(1, 4), (2, 5)]
+            .iter()
+            .map(|&(from, to)| Match {
+                offset: from,
+                text: &line[from..to],
+                line,
+                line_number: 1,
+                column: from,
+            })
+            .collect();
+        assert_eq!("a\x1b[1;31mbcd\x1b[0mef", paint(line, &overlapping));
+    }
 }
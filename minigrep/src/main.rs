@@ -1,14 +1,37 @@
-use minigrep::Config;
-use std::{env, process};
+use minigrep::{Config, Outcome};
+use std::{env, io, process};
 
 fn main() {
-    let config = Config::new(env::args()).unwrap_or_else(|e| {
+    if env::args().nth(1).as_deref() == Some("--server") {
+        let stdin = io::stdin();
+        if let Err(e) = minigrep::server::serve(stdin.lock(), io::stdout()) {
+            eprintln!("Server error: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    let config = Config::load(env::args()).unwrap_or_else(|e| {
         eprintln!("Problem parsing arguments: {}", e);
         process::exit(1);
     });
 
-    if let Err(e) = minigrep::run(config) {
-        eprintln!("Application error: {}", e);
-        process::exit(1);
+    let result = if config.interactive {
+        minigrep::tui::run(config).map(|_| Outcome::Complete)
+    } else {
+        minigrep::run(config)
     };
+
+    match result {
+        Ok(Outcome::Complete) => {}
+        Ok(Outcome::Truncated(truncation)) => {
+            for note in truncation.notes() {
+                eprintln!("Note: {}", note);
+            }
+        }
+        Err(e) => {
+            eprintln!("Application error: {}", e);
+            process::exit(1);
+        }
+    }
 }
@@ -0,0 +1,638 @@
+//! # server
+//!
+//! A long-running search server, started with `minigrep --server`, that
+//! speaks JSON-RPC 2.0 with one message per line. Directory listings and
+//! file contents are kept in memory between requests, up to a fixed budget
+//! beyond which the least recently used are dropped, and are reloaded only
+//! when their modification time or size changes.
+//!
+//! Supported methods:
+//!
+//! - `search` with `{"query", "paths", "case_sensitive"?, "smart_case"?,
+//!   "ignore"?}` streams a `results` notification for every file with
+//!   matches, then responds with `{"files", "matches"}` once the search has
+//!   finished. Directories are walked as the command line walks them,
+//!   skipping entries that match the `ignore` globs. A search sent under
+//!   the id of one still running replaces it, and only the new search
+//!   responds. A search sent as a notification, without an id, is ignored,
+//!   since it could be neither answered nor cancelled
+//! - `cancel` with `{"id"}` stops an in-flight search at the next line it
+//!   reaches, and it then responds with a "Request cancelled" error
+//! - `shutdown` cancels everything in flight and stops the server
+//!
+//! The command line's `--max-count` and `--timeout` don't apply to the
+//! server: a client bounds a search by cancelling it instead.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::io::Cursor;
+//!
+//! let request = r#"{"jsonrpc":"2.0","id":1,"method":"search","params":{"query":"frog","paths":["poem.txt"]}}"#;
+//! let mut output = Vec::new();
+//! minigrep::server::serve(Cursor::new(request), &mut output).unwrap();
+//!
+//! let output = String::from_utf8(output).unwrap();
+//! let mut messages = output.lines();
+//! assert!(messages.next().unwrap().contains(r#""method":"results""#));
+//! assert!(messages.next().unwrap().contains(r#""result":{"files":1,"matches":1}"#));
+//! ```
+
+use crate::{Config, Match, Walk};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const REQUEST_CANCELLED: i64 = -32800;
+
+/// Most directory listings kept in memory at once
+const MAX_CACHED_DIRS: usize = 4096;
+/// Most bytes of file contents kept in memory at once
+const MAX_CACHED_BYTES: usize = 256 << 20;
+
+/// Serve requests read from `input`, writing responses and notifications
+/// to `output`, until the input is exhausted or a `shutdown` arrives.
+/// Searches run on their own threads, and are all finished before this
+/// returns.
+pub fn serve<R, W>(input: R, output: W) -> io::Result<()>
+where
+    R: BufRead,
+    W: Write + Send,
+{
+    let out = Mutex::new(output);
+    let cache = Cache::default();
+    let inflight = Mutex::new(HashMap::new());
+
+    thread::scope(|scope| {
+        let server = Server {
+            out: &out,
+            cache: &cache,
+            inflight: &inflight,
+        };
+
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if !server.handle(scope, &line) {
+                break;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    query: String,
+    paths: Vec<PathBuf>,
+    #[serde(default = "default_case_sensitive")]
+    case_sensitive: bool,
+    #[serde(default)]
+    smart_case: bool,
+    #[serde(default)]
+    ignore: Vec<String>,
+}
+
+fn default_case_sensitive() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+struct CancelParams {
+    id: Value,
+}
+
+struct Server<'a, W> {
+    out: &'a Mutex<W>,
+    cache: &'a Cache,
+    inflight: &'a Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl<'a, W: Write + Send> Server<'a, W> {
+    /// Handle a single message, returning whether to keep serving.
+    fn handle<'scope>(&self, scope: &'scope thread::Scope<'scope, '_>, message: &str) -> bool
+    where
+        'a: 'scope,
+    {
+        let request: Request = match serde_json::from_str::<Value>(message) {
+            Ok(value) => match serde_json::from_value(value) {
+                Ok(request) => request,
+                Err(e) => {
+                    self.error(&Value::Null, INVALID_REQUEST, &e.to_string());
+                    return true;
+                }
+            },
+            Err(e) => {
+                self.error(&Value::Null, PARSE_ERROR, &e.to_string());
+                return true;
+            }
+        };
+
+        match request.method.as_str() {
+            "search" => self.search(scope, request),
+            "cancel" => self.cancel(request),
+            "shutdown" => {
+                for cancel in self.inflight.lock().unwrap().values() {
+                    cancel.store(true, Ordering::SeqCst);
+                }
+                if let Some(id) = &request.id {
+                    self.reply(id, Value::Null);
+                }
+                return false;
+            }
+            _ => {
+                if let Some(id) = &request.id {
+                    self.error(id, METHOD_NOT_FOUND, "Method not found");
+                }
+            }
+        }
+
+        true
+    }
+
+    fn search<'scope>(&self, scope: &'scope thread::Scope<'scope, '_>, request: Request)
+    where
+        'a: 'scope,
+    {
+        let id = match request.id {
+            Some(id) => id,
+            None => return,
+        };
+        let params: SearchParams = match serde_json::from_value(request.params) {
+            Ok(params) => params,
+            Err(e) => return self.error(&id, INVALID_PARAMS, &e.to_string()),
+        };
+
+        let key = id.to_string();
+        let cancel = Arc::new(AtomicBool::new(false));
+        if let Some(previous) = self
+            .inflight
+            .lock()
+            .unwrap()
+            .insert(key.clone(), Arc::clone(&cancel))
+        {
+            // A reused id supersedes whatever was running under it
+            previous.store(true, Ordering::SeqCst);
+        }
+
+        let out = self.out;
+        let cache = self.cache;
+        let inflight = self.inflight;
+        scope.spawn(move || {
+            let server = Server {
+                out,
+                cache,
+                inflight,
+            };
+            let outcome = search(cache, &params, &cancel, |path, matches| {
+                server.notify(
+                    "results",
+                    json!({
+                        "id": id,
+                        "path": path,
+                        "matches": matches.iter().map(|m| json!({
+                            "line_number": m.line_number,
+                            "offset": m.offset,
+                            "text": m.text,
+                            "line": m.line,
+                        })).collect::<Vec<_>>(),
+                    }),
+                );
+            });
+
+            let superseded = {
+                let mut inflight = inflight.lock().unwrap();
+                let current = inflight.get(&key).is_some_and(|c| Arc::ptr_eq(c, &cancel));
+                if current {
+                    inflight.remove(&key);
+                }
+                !current
+            };
+            // The search that replaced this one answers for the id
+            if superseded {
+                return;
+            }
+
+            match outcome {
+                Some(summary) => server.reply(
+                    &id,
+                    json!({ "files": summary.files, "matches": summary.matches }),
+                ),
+                None => server.error(&id, REQUEST_CANCELLED, "Request cancelled"),
+            }
+        });
+    }
+
+    fn cancel(&self, request: Request) {
+        let params: CancelParams = match serde_json::from_value(request.params) {
+            Ok(params) => params,
+            Err(e) => {
+                if let Some(id) = &request.id {
+                    self.error(id, INVALID_PARAMS, &e.to_string());
+                }
+                return;
+            }
+        };
+
+        let found = match self.inflight.lock().unwrap().get(&params.id.to_string()) {
+            Some(cancel) => {
+                cancel.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        };
+
+        if let Some(id) = &request.id {
+            self.reply(id, json!({ "cancelled": found }));
+        }
+    }
+
+    fn reply(&self, id: &Value, result: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+    }
+
+    fn error(&self, id: &Value, code: i64, message: &str) {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        }));
+    }
+
+    fn notify(&self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn send(&self, message: Value) {
+        let mut out = self.out.lock().unwrap();
+        // A client that went away can't be told about it
+        let _ = writeln!(out, "{}", message).and_then(|_| out.flush());
+    }
+}
+
+struct Summary {
+    files: usize,
+    matches: usize,
+}
+
+/// Search every file under `params.paths`, calling `emit` for each file
+/// with matches. Returns `None` if `cancel` was raised before finishing,
+/// which is checked before every line.
+fn search<F>(
+    cache: &Cache,
+    params: &SearchParams,
+    cancel: &AtomicBool,
+    mut emit: F,
+) -> Option<Summary>
+where
+    F: FnMut(&Path, &[Match]),
+{
+    let config = Config {
+        query: params.query.clone(),
+        case_sensitive: params.case_sensitive,
+        smart_case: params.smart_case,
+        ..Config::default()
+    };
+
+    let mut summary = Summary {
+        files: 0,
+        matches: 0,
+    };
+    let list = |dir: &Path| cache.list(dir).map(|entries| entries.to_vec());
+
+    for root in &params.paths {
+        let walk = if root.is_dir() {
+            match crate::walk_with(root, &params.ignore, None, &list) {
+                Ok(walk) => walk,
+                Err(_) => continue,
+            }
+        } else {
+            Walk::file(root)
+        };
+
+        for path in walk {
+            if cancel.load(Ordering::SeqCst) {
+                return None;
+            }
+
+            // Unreadable and non-UTF-8 files are skipped, as they can't match
+            let contents = match cache.read(&path) {
+                Ok(contents) => contents,
+                Err(_) => continue,
+            };
+            let mut matches = Vec::new();
+            for line in config.matches_by_line(&contents) {
+                if cancel.load(Ordering::SeqCst) {
+                    return None;
+                }
+                matches.extend(line);
+            }
+
+            if !matches.is_empty() {
+                summary.files += 1;
+                summary.matches += matches.len();
+                emit(&path, &matches);
+            }
+        }
+    }
+
+    Some(summary)
+}
+
+type Stamp = (SystemTime, u64);
+
+/// Values keyed by path, each with the stamp it was read at. Once their
+/// total weight would pass the capacity, the least recently used are
+/// dropped to make room.
+struct Cached<T> {
+    entries: HashMap<PathBuf, Entry<T>>,
+    weight: usize,
+    capacity: usize,
+    clock: u64,
+}
+
+struct Entry<T> {
+    stamp: Stamp,
+    value: Arc<T>,
+    weight: usize,
+    used: u64,
+}
+
+impl<T> Cached<T> {
+    fn new(capacity: usize) -> Self {
+        Cached {
+            entries: HashMap::new(),
+            weight: 0,
+            capacity,
+            clock: 0,
+        }
+    }
+
+    /// The value cached for `path`, if it was read at `stamp`.
+    fn get(&mut self, path: &Path, stamp: Stamp) -> Option<Arc<T>> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(path)?;
+        if entry.stamp != stamp {
+            return None;
+        }
+        entry.used = self.clock;
+        Some(Arc::clone(&entry.value))
+    }
+
+    /// Cache `value` for `path`, unless it alone outweighs the capacity.
+    fn insert(&mut self, path: PathBuf, stamp: Stamp, value: &Arc<T>, weight: usize) {
+        if let Some(stale) = self.entries.remove(&path) {
+            self.weight -= stale.weight;
+        }
+        if weight > self.capacity {
+            return;
+        }
+
+        while self.weight + weight > self.capacity {
+            let oldest = match self.entries.iter().min_by_key(|(_, entry)| entry.used) {
+                Some((oldest, _)) => oldest.clone(),
+                None => break,
+            };
+            if let Some(evicted) = self.entries.remove(&oldest) {
+                self.weight -= evicted.weight;
+            }
+        }
+
+        self.clock += 1;
+        self.weight += weight;
+        self.entries.insert(
+            path,
+            Entry {
+                stamp,
+                value: Arc::clone(value),
+                weight,
+                used: self.clock,
+            },
+        );
+    }
+}
+
+/// Directory listings and file contents, keyed by path and invalidated
+/// whenever the modification time or length on disk changes. Listings are
+/// counted against the budget one apiece, and contents by their length.
+struct Cache {
+    dirs: Mutex<Cached<Vec<(PathBuf, bool)>>>,
+    files: Mutex<Cached<String>>,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Cache::new(MAX_CACHED_DIRS, MAX_CACHED_BYTES)
+    }
+}
+
+impl Cache {
+    fn new(max_dirs: usize, max_bytes: usize) -> Self {
+        Cache {
+            dirs: Mutex::new(Cached::new(max_dirs)),
+            files: Mutex::new(Cached::new(max_bytes)),
+        }
+    }
+
+    /// List a directory as [`crate::read_dir`] does.
+    fn list(&self, dir: &Path) -> io::Result<Arc<Vec<(PathBuf, bool)>>> {
+        let stamp = stamp(dir)?;
+        if let Some(entries) = self.dirs.lock().unwrap().get(dir, stamp) {
+            return Ok(entries);
+        }
+
+        let entries = Arc::new(crate::read_dir(dir)?);
+        self.dirs
+            .lock()
+            .unwrap()
+            .insert(dir.to_path_buf(), stamp, &entries, 1);
+        Ok(entries)
+    }
+
+    /// Read a file's contents.
+    fn read(&self, file: &Path) -> io::Result<Arc<String>> {
+        let stamp = stamp(file)?;
+        if let Some(contents) = self.files.lock().unwrap().get(file, stamp) {
+            return Ok(contents);
+        }
+
+        let contents = Arc::new(fs::read_to_string(file)?);
+        self.files
+            .lock()
+            .unwrap()
+            .insert(file.to_path_buf(), stamp, &contents, contents.len());
+        Ok(contents)
+    }
+}
+
+fn stamp(path: &Path) -> io::Result<Stamp> {
+    let metadata = fs::metadata(path)?;
+    Ok((metadata.modified()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn params(query: &str) -> SearchParams {
+        SearchParams {
+            query: query.to_string(),
+            paths: vec![PathBuf::from("poem.txt")],
+            case_sensitive: true,
+            smart_case: false,
+            ignore: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn search_streams_each_file() {
+        let cache = Cache::default();
+        let mut emitted = Vec::new();
+        let summary = search(
+            &cache,
+            &params("nobody"),
+            &AtomicBool::new(false),
+            |path, m| emitted.push((path.to_path_buf(), m.len())),
+        )
+        .unwrap();
+
+        assert_eq!(vec![(PathBuf::from("poem.txt"), 2)], emitted);
+        assert_eq!(1, summary.files);
+        assert_eq!(2, summary.matches);
+    }
+
+    #[test]
+    fn search_honours_smart_case() {
+        let cache = Cache::default();
+        let mut params = params("how");
+        params.smart_case = true;
+        let summary = search(&cache, &params, &AtomicBool::new(false), |_, _| {}).unwrap();
+        assert_eq!(2, summary.matches);
+    }
+
+    #[test]
+    fn search_stops_when_cancelled() {
+        let cache = Cache::default();
+        let outcome = search(&cache, &params("nobody"), &AtomicBool::new(true), |_, _| {
+            panic!("cancelled searches shouldn't emit results")
+        });
+        assert!(outcome.is_none());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn search_walks_directories_as_the_cli_does() {
+        let root =
+            std::env::temp_dir().join(format!("minigrep-server-walk-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join("a.txt"), "needle\n").unwrap();
+        fs::write(root.join("target/b.txt"), "needle\n").unwrap();
+        // A link back up the tree would be walked forever if followed
+        std::os::unix::fs::symlink(&root, root.join("loop")).unwrap();
+
+        let cache = Cache::default();
+        let mut params = params("needle");
+        params.paths = vec![root.clone()];
+        params.ignore = vec![String::from("target/")];
+        let mut emitted = Vec::new();
+        let summary = search(&cache, &params, &AtomicBool::new(false), |path, _| {
+            emitted.push(path.to_path_buf())
+        });
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(vec![root.join("a.txt")], emitted);
+        assert_eq!(1, summary.unwrap().files);
+    }
+
+    #[test]
+    fn cache_reuses_unchanged_files() {
+        let cache = Cache::default();
+        let first = cache.read(Path::new("poem.txt")).unwrap();
+        let second = cache.read(Path::new("poem.txt")).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+    }
+
+    #[test]
+    fn cache_drops_least_recently_used() {
+        let stamp = (SystemTime::UNIX_EPOCH, 0);
+        let mut cached = Cached::new(10);
+        cached.insert(PathBuf::from("a"), stamp, &Arc::new("aaaa"), 4);
+        cached.insert(PathBuf::from("b"), stamp, &Arc::new("bbbb"), 4);
+        assert!(cached.get(Path::new("a"), stamp).is_some());
+
+        // Making room for "c" drops "b", which was used longest ago
+        cached.insert(PathBuf::from("c"), stamp, &Arc::new("cccc"), 4);
+        assert!(cached.get(Path::new("b"), stamp).is_none());
+        assert!(cached.get(Path::new("a"), stamp).is_some());
+        assert_eq!(8, cached.weight);
+
+        // Anything larger than the whole budget isn't kept at all
+        cached.insert(PathBuf::from("d"), stamp, &Arc::new("d"), 11);
+        assert!(cached.get(Path::new("d"), stamp).is_none());
+        assert_eq!(2, cached.entries.len());
+
+        // Nor is a value read at a different stamp handed out
+        let later = (SystemTime::UNIX_EPOCH, 1);
+        assert!(cached.get(Path::new("a"), later).is_none());
+    }
+
+    #[test]
+    fn serve_reports_protocol_errors() {
+        let input = "not json\n{\"jsonrpc\":\"2.0\",\"id\":7,\"method\":\"nope\"}\n";
+        let mut output = Vec::new();
+        serve(Cursor::new(input), &mut output).unwrap();
+
+        let replies: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(PARSE_ERROR, replies[0]["error"]["code"]);
+        assert_eq!(7, replies[1]["id"]);
+        assert_eq!(METHOD_NOT_FOUND, replies[1]["error"]["code"]);
+    }
+
+    #[test]
+    fn serve_never_answers_notifications() {
+        let input = concat!(
+            r#"{"jsonrpc":"2.0","method":"search","params":{"query":"frog","paths":["poem.txt"]}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","method":"search","params":{}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","id":1,"method":"shutdown"}"#,
+        );
+        let mut output = Vec::new();
+        serve(Cursor::new(input), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            vec![r#"{"id":1,"jsonrpc":"2.0","result":null}"#],
+            output.lines().collect::<Vec<_>>()
+        );
+    }
+}
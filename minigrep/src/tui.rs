@@ -0,0 +1,487 @@
+//! # tui
+//!
+//! An interactive terminal UI, started with `minigrep --interactive`. The
+//! query is typed at a prompt and results update with every keystroke,
+//! found the same way as by the command line, flags and all, so the lines
+//! shown are exactly those `minigrep` would print. When searching fields,
+//! each matching record is shown by the line it starts on.
+//!
+//! Only a single file can be browsed, not a directory. `--max-count` and
+//! `--timeout` don't apply here, as the file is searched afresh on every
+//! keystroke and all of its results can be scrolled through.
+//!
+//! Keys:
+//!
+//! - typing edits the query, and `Backspace` deletes from it
+//! - `Up`/`Down` and `PageUp`/`PageDown` move through the results
+//! - `Enter` opens `$EDITOR` at the selected line
+//! - `Esc` or `Ctrl-C` quits
+
+use crate::{matching_lines, Config};
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::Command;
+
+/// Run the interactive UI until the user quits.
+pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
+    if Path::new(&config.filename).is_dir() {
+        return Err(format!(
+            "{} is a directory, but only a single file can be browsed interactively",
+            config.filename
+        )
+        .into());
+    }
+    let contents = fs::read_to_string(&config.filename)?;
+    let mut app = App::new(config, contents);
+    let mut screen = Screen::enter()?;
+
+    loop {
+        let (width, height) = terminal::size()?;
+        app.draw(&mut io::stdout(), width as usize, height as usize)?;
+
+        let key = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            _ => continue,
+        };
+
+        match app.handle(key, height as usize) {
+            Action::Continue => {}
+            Action::Quit => return Ok(()),
+            Action::Open(line_number) => {
+                screen.suspend()?;
+                open_editor(&app.config.filename, line_number)?;
+                screen = Screen::enter()?;
+            }
+        }
+    }
+}
+
+/// What the UI should do after handling a key.
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    Continue,
+    Quit,
+    /// Open the file in an editor at this one-based line number
+    Open(usize),
+}
+
+/// The state of the UI, independent of any terminal.
+pub struct App {
+    config: Config,
+    contents: String,
+    /// Byte offset at which each line of `contents` starts
+    line_starts: Vec<usize>,
+    /// Zero-based indices of the lines matching the query
+    results: Vec<usize>,
+    /// Why the query can't be searched for, such as a missing field
+    error: Option<String>,
+    selected: usize,
+    scroll: usize,
+}
+
+impl App {
+    pub fn new(config: Config, contents: String) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(
+            contents
+                .match_indices('\n')
+                .map(|(i, _)| i + 1)
+                .filter(|&start| start < contents.len()),
+        );
+
+        let mut app = App {
+            config,
+            contents,
+            line_starts,
+            results: Vec::new(),
+            error: None,
+            selected: 0,
+            scroll: 0,
+        };
+        app.refresh();
+        app
+    }
+
+    pub fn query(&self) -> &str {
+        &self.config.query
+    }
+
+    /// One-based line numbers of the current results.
+    pub fn results(&self) -> Vec<usize> {
+        self.results.iter().map(|i| i + 1).collect()
+    }
+
+    /// One-based line number of the selected result, if there is one.
+    pub fn selected(&self) -> Option<usize> {
+        self.results.get(self.selected).map(|i| i + 1)
+    }
+
+    /// Update the state for a key press, given the terminal height.
+    pub fn handle(&mut self, key: KeyEvent, height: usize) -> Action {
+        let page = Layout::new(height).list.max(1);
+
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Action::Quit
+            }
+            KeyCode::Esc => return Action::Quit,
+            KeyCode::Enter => {
+                if let Some(line_number) = self.selected() {
+                    return Action::Open(line_number);
+                }
+            }
+            KeyCode::Up => self.select(self.selected.saturating_sub(1)),
+            KeyCode::Down => self.select(self.selected + 1),
+            KeyCode::PageUp => self.select(self.selected.saturating_sub(page)),
+            KeyCode::PageDown => self.select(self.selected + page),
+            KeyCode::Backspace => {
+                self.config.query.pop();
+                self.refresh();
+            }
+            KeyCode::Char(c) => {
+                self.config.query.push(c);
+                self.refresh();
+            }
+            _ => {}
+        }
+
+        Action::Continue
+    }
+
+    /// Lines around the selected result, centred on it where possible, as
+    /// pairs of one-based line number and text.
+    pub fn preview(&self, height: usize) -> Vec<(usize, &str)> {
+        let center = match self.results.get(self.selected) {
+            Some(&line) => line,
+            None => return Vec::new(),
+        };
+        let total = self.line_starts.len();
+        let first = center
+            .saturating_sub(height / 2)
+            .min(total.saturating_sub(height));
+
+        (first..total.min(first + height))
+            .map(|i| (i + 1, self.line(i)))
+            .collect()
+    }
+
+    fn select(&mut self, index: usize) {
+        self.selected = index.min(self.results.len().saturating_sub(1));
+    }
+
+    fn refresh(&mut self) {
+        let path = Path::new(&self.config.filename);
+        match matching_lines(&self.config, &self.contents, path) {
+            Ok(results) => {
+                self.results = results;
+                self.error = None;
+            }
+            Err(e) => {
+                self.results.clear();
+                self.error = Some(e.to_string());
+            }
+        }
+
+        self.selected = 0;
+        self.scroll = 0;
+    }
+
+    fn line(&self, index: usize) -> &str {
+        let start = self.line_starts[index];
+        let end = self
+            .line_starts
+            .get(index + 1)
+            .map_or(self.contents.len(), |&next| next - 1);
+        let line = &self.contents[start..end.max(start)];
+        line.strip_suffix('\r').unwrap_or(line)
+    }
+
+    fn draw<W: Write>(&mut self, out: &mut W, width: usize, height: usize) -> io::Result<()> {
+        let layout = Layout::new(height);
+
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + layout.list {
+            self.scroll = self.selected + 1 - layout.list;
+        }
+
+        queue!(out, Clear(ClearType::All), MoveTo(0, 0))?;
+        let prompt = match &self.error {
+            Some(error) => format!("> {}  ({})", self.config.query, error),
+            None => format!(
+                "> {}  ({} of {} lines)",
+                self.config.query,
+                self.results.len(),
+                self.line_starts.len()
+            ),
+        };
+        queue!(out, Print(fit(&prompt, width)))?;
+
+        for (row, (i, &line)) in self
+            .results
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(layout.list)
+            .enumerate()
+        {
+            let text = format!("{:>5}: {}", line + 1, self.line(line));
+            queue!(out, MoveTo(0, (row + 1) as u16))?;
+            if i == self.selected {
+                queue!(
+                    out,
+                    SetAttribute(Attribute::Reverse),
+                    Print(fit(&text, width)),
+                    SetAttribute(Attribute::Reset)
+                )?;
+            } else {
+                queue!(out, Print(fit(&text, width)))?;
+            }
+        }
+
+        queue!(
+            out,
+            MoveTo(0, (layout.list + 1) as u16),
+            Print("─".repeat(width))
+        )?;
+
+        let selected = self.selected();
+        for (row, (line_number, text)) in self.preview(layout.preview).into_iter().enumerate() {
+            let text = format!("{:>5}  {}", line_number, text);
+            queue!(out, MoveTo(0, (layout.list + 2 + row) as u16))?;
+            if Some(line_number) == selected {
+                queue!(
+                    out,
+                    SetAttribute(Attribute::Bold),
+                    Print(fit(&text, width)),
+                    SetAttribute(Attribute::Reset)
+                )?;
+            } else {
+                queue!(out, Print(fit(&text, width)))?;
+            }
+        }
+
+        out.flush()
+    }
+}
+
+/// Rows given to each pane: the prompt takes one, the divider another,
+/// and the rest is split between the results list and the preview.
+struct Layout {
+    list: usize,
+    preview: usize,
+}
+
+impl Layout {
+    fn new(height: usize) -> Self {
+        let body = height.saturating_sub(2);
+        let list = body / 2;
+        Layout {
+            list,
+            preview: body - list,
+        }
+    }
+}
+
+/// Expand tabs and cut text down to the terminal width.
+fn fit(text: &str, width: usize) -> String {
+    text.replace('\t', "    ").chars().take(width).collect()
+}
+
+/// Puts the terminal into raw mode on the alternate screen, and restores
+/// it when suspended or dropped.
+struct Screen {
+    active: bool,
+}
+
+impl Screen {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, Hide)?;
+        Ok(Screen { active: true })
+    }
+
+    fn suspend(&mut self) -> io::Result<()> {
+        if self.active {
+            self.active = false;
+            execute!(io::stdout(), Show, LeaveAlternateScreen)?;
+            terminal::disable_raw_mode()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = self.suspend();
+    }
+}
+
+/// Open `filename` at `line_number` in `$EDITOR`, falling back to `vi`.
+/// The editor may be given with arguments, such as `code --wait`.
+fn open_editor(filename: &str, line_number: usize) -> Result<(), Box<dyn Error>> {
+    let editor = env::var("EDITOR").unwrap_or_else(|_| String::from("vi"));
+    let mut words = editor.split_whitespace();
+    let program = words.next().ok_or("EDITOR is empty")?;
+
+    let status = Command::new(program)
+        .args(words)
+        .arg(format!("+{}", line_number))
+        .arg(filename)
+        .status()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} exited with {}", program, status).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENTS: &str = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Trust me.";
+
+    fn app_for(query: &str, case_sensitive: bool) -> App {
+        let config = Config {
+            query: String::from(query),
+            case_sensitive,
+            ..Default::default()
+        };
+        App::new(config, String::from(CONTENTS))
+    }
+
+    fn press(app: &mut App, code: KeyCode) -> Action {
+        app.handle(KeyEvent::new(code, KeyModifiers::NONE), 24)
+    }
+
+    #[test]
+    fn results_match_library_search() {
+        assert_eq!(vec![2], app_for("duct", true).results());
+        assert_eq!(vec![1, 4], app_for("rUsT", false).results());
+    }
+
+    #[test]
+    fn results_follow_every_flag() {
+        let config = Config {
+            query: String::from("^[PT]"),
+            regex: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            vec![3, 4],
+            App::new(config, String::from(CONTENTS)).results()
+        );
+
+        // Records are found by their fields, wherever they start
+        let contents = "name,motto\nferris,\"safe,\nfast\"\nrust,fast\n";
+        let config = Config {
+            query: String::from("fast"),
+            filename: String::from("crabs.csv"),
+            fields: vec![crate::csv::Field::Name(String::from("motto"))],
+            ..Default::default()
+        };
+        let mut app = App::new(config, String::from(contents));
+        assert_eq!(vec![2, 4], app.results());
+
+        app.config.fields = vec![crate::csv::Field::Name(String::from("email"))];
+        app.refresh();
+        assert!(app.results().is_empty());
+        assert_eq!(
+            Some("No column named `email` in the header"),
+            app.error.as_deref()
+        );
+    }
+
+    #[test]
+    fn directories_are_refused() {
+        let config = Config {
+            filename: String::from("src"),
+            ..Default::default()
+        };
+        let error = run(config).unwrap_err().to_string();
+        assert!(error.starts_with("src is a directory"));
+    }
+
+    #[test]
+    fn typing_updates_results() {
+        let mut app = app_for("", true);
+        assert_eq!(vec![1, 2, 3, 4], app.results());
+
+        for c in "st".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        assert_eq!("st", app.query());
+        assert_eq!(vec![1, 2, 4], app.results());
+
+        press(&mut app, KeyCode::Backspace);
+        press(&mut app, KeyCode::Backspace);
+        assert_eq!("", app.query());
+        assert_eq!(vec![1, 2, 3, 4], app.results());
+    }
+
+    #[test]
+    fn selection_stays_within_results() {
+        let mut app = app_for("st", true);
+        press(&mut app, KeyCode::Up);
+        assert_eq!(Some(1), app.selected());
+
+        press(&mut app, KeyCode::PageDown);
+        assert_eq!(Some(4), app.selected());
+
+        press(&mut app, KeyCode::Up);
+        assert_eq!(Some(2), app.selected());
+    }
+
+    #[test]
+    fn enter_opens_selected_line() {
+        let mut app = app_for("three", true);
+        assert_eq!(Action::Open(3), press(&mut app, KeyCode::Enter));
+
+        let mut app = app_for("nothing", true);
+        assert_eq!(Action::Continue, press(&mut app, KeyCode::Enter));
+        assert_eq!(Action::Quit, press(&mut app, KeyCode::Esc));
+    }
+
+    #[test]
+    fn preview_surrounds_selection() {
+        let app = app_for("three", true);
+        assert_eq!(
+            vec![
+                (2, "safe, fast, productive."),
+                (3, "Pick three."),
+                (4, "Trust me.")
+            ],
+            app.preview(3)
+        );
+        assert_eq!(4, app.preview(10).len());
+    }
+
+    #[test]
+    fn smart_case_follows_typed_query() {
+        let config = Config {
+            smart_case: true,
+            ..Default::default()
+        };
+        let mut app = App::new(config, String::from(CONTENTS));
+        press(&mut app, KeyCode::Char('r'));
+        assert_eq!(vec![1, 2, 3, 4], app.results());
+
+        press(&mut app, KeyCode::Backspace);
+        press(&mut app, KeyCode::Char('R'));
+        assert_eq!(vec![1], app.results());
+    }
+}
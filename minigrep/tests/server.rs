@@ -0,0 +1,199 @@
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{ChildStdin, Command, Stdio};
+
+/// A scratch directory under the system temp dir, removed on drop.
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("minigrep-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        Scratch(dir)
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn send(stdin: &mut ChildStdin, message: Value) {
+    writeln!(stdin, "{}", message).unwrap();
+    stdin.flush().unwrap();
+}
+
+#[test]
+fn server_streams_results_over_pipes() {
+    let scratch = Scratch::new("server");
+    fs::write(scratch.0.join("a.txt"), "alpha\nneedle one\n").unwrap();
+    fs::write(
+        scratch.0.join("nested/b.txt"),
+        "needle two\nbeta\nNEEDLE three\n",
+    )
+    .unwrap();
+    fs::write(scratch.0.join(".hidden"), "needle hidden\n").unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .arg("--server")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let mut replies = BufReader::new(child.stdout.take().unwrap()).lines();
+    let mut next =
+        move || -> Value { serde_json::from_str(&replies.next().unwrap().unwrap()).unwrap() };
+
+    send(
+        &mut stdin,
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "search",
+            "params": { "query": "needle", "paths": [scratch.0] },
+        }),
+    );
+
+    let first = next();
+    assert_eq!("results", first["method"]);
+    assert!(first["params"]["path"].as_str().unwrap().ends_with("a.txt"));
+    assert_eq!(2, first["params"]["matches"][0]["line_number"]);
+    assert_eq!(6, first["params"]["matches"][0]["offset"]);
+
+    let second = next();
+    assert!(second["params"]["path"]
+        .as_str()
+        .unwrap()
+        .ends_with("b.txt"));
+    assert_eq!(1, second["params"]["matches"].as_array().unwrap().len());
+
+    let done = next();
+    assert_eq!(1, done["id"]);
+    assert_eq!(json!({ "files": 2, "matches": 2 }), done["result"]);
+
+    // Changes on disk invalidate the warm copy
+    fs::write(scratch.0.join("a.txt"), "alpha\nneedle one\nneedle again\n").unwrap();
+    send(
+        &mut stdin,
+        json!({
+            "jsonrpc": "2.0",
+            "id": "again",
+            "method": "search",
+            "params": { "query": "needle", "paths": [scratch.0], "smart_case": true },
+        }),
+    );
+    let mut matches = 0;
+    let done = loop {
+        let message = next();
+        if message["method"] == "results" {
+            matches += message["params"]["matches"].as_array().unwrap().len();
+        } else {
+            break message;
+        }
+    };
+    assert_eq!("again", done["id"]);
+    assert_eq!(4, matches);
+    assert_eq!(4, done["result"]["matches"]);
+
+    // Nothing is running under this id any more
+    send(
+        &mut stdin,
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "cancel", "params": { "id": 1 } }),
+    );
+    assert_eq!(json!({ "cancelled": false }), next()["result"]);
+
+    send(
+        &mut stdin,
+        json!({ "jsonrpc": "2.0", "id": 3, "method": "shutdown" }),
+    );
+    assert_eq!(3, next()["id"]);
+    assert!(child.wait().unwrap().success());
+}
+
+/// Start a server on a scratch directory holding one large file, long
+/// enough to search that requests sent straight after it arrive mid-file.
+fn large_search(name: &str) -> (Scratch, std::process::Child) {
+    let scratch = Scratch::new(name);
+    fs::write(scratch.0.join("large.txt"), "haystack\n".repeat(3_000_000)).unwrap();
+    fs::write(scratch.0.join("nested/small.txt"), "needle\n").unwrap();
+
+    let child = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .arg("--server")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    (scratch, child)
+}
+
+#[test]
+fn server_cancels_a_search_in_flight() {
+    let (scratch, mut child) = large_search("cancel");
+    let mut stdin = child.stdin.take().unwrap();
+    let mut replies = BufReader::new(child.stdout.take().unwrap()).lines();
+
+    send(
+        &mut stdin,
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "search",
+            "params": { "query": "needle", "paths": [scratch.0.join("large.txt")] },
+        }),
+    );
+    send(
+        &mut stdin,
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "cancel", "params": { "id": 1 } }),
+    );
+
+    let messages: Vec<Value> = (0..2)
+        .map(|_| serde_json::from_str(&replies.next().unwrap().unwrap()).unwrap())
+        .collect();
+    let cancel = messages.iter().find(|m| m["id"] == 2).unwrap();
+    assert_eq!(json!({ "cancelled": true }), cancel["result"]);
+    let search = messages.iter().find(|m| m["id"] == 1).unwrap();
+    assert_eq!("Request cancelled", search["error"]["message"]);
+
+    send(
+        &mut stdin,
+        json!({ "jsonrpc": "2.0", "id": 3, "method": "shutdown" }),
+    );
+    let done: Value = serde_json::from_str(&replies.next().unwrap().unwrap()).unwrap();
+    assert_eq!(3, done["id"]);
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn server_answers_a_reused_id_once() {
+    let (scratch, mut child) = large_search("reuse");
+    let mut stdin = child.stdin.take().unwrap();
+
+    for path in &[scratch.0.join("large.txt"), scratch.0.join("nested")] {
+        send(
+            &mut stdin,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "search",
+                "params": { "query": "needle", "paths": [path] },
+            }),
+        );
+    }
+    drop(stdin);
+
+    // Only the second search answers, however the two finish
+    let output = child.wait_with_output().unwrap();
+    let messages: Vec<Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let answers: Vec<&Value> = messages.iter().filter(|m| m["id"] == 1).collect();
+    assert_eq!(1, answers.len());
+    assert_eq!(json!({ "files": 1, "matches": 1 }), answers[0]["result"]);
+}